@@ -1,9 +1,10 @@
 
 use solana_program::{
-    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, pubkey::Pubkey,
+    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult,
+    program_error::PrintProgramError, pubkey::Pubkey,
 };
 
-use crate::processor::Processor;
+use crate::{error::LotteryError, processor::Processor};
 
 entrypoint!(process_instruction);
 fn process_instruction(
@@ -11,5 +12,9 @@ fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    Processor::process(program_id, accounts, instruction_data)
+    if let Err(error) = Processor::process(program_id, accounts, instruction_data) {
+        error.print::<LotteryError>();
+        return Err(error);
+    }
+    Ok(())
 }
\ No newline at end of file
@@ -1,6 +1,11 @@
-use solana_program::program_error::ProgramError;
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive as DecodeFromPrimitive;
+use solana_program::{
+    decode_error::DecodeError, msg, program_error::PrintProgramError,
+    program_error::ProgramError,
+};
 use thiserror::Error;
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq, FromPrimitive)]
 pub enum LotteryError {
     /// Invalid instruction
     #[error("Invalid Instruction")]
@@ -12,6 +17,117 @@ pub enum LotteryError {
     AccountNotWritable,
     #[error("Ticket has been sold out")]
     LotterySoldOut,
+    /// Buying after the ended slot or end timestamp
+    #[error("Lottery has ended")]
+    LotteryEnded,
+    /// Drawing before the ended slot or end timestamp with tickets left
+    #[error("Lottery hasn't ended yet")]
+    LotteryNotEnded,
+    /// Buying or drawing a lottery that isn't open anymore
+    #[error("Lottery isn't open")]
+    LotteryNotOpen,
+    #[error("Lottery is already drawn")]
+    AlreadyDrawn,
+    /// Paying out a lottery without winning numbers
+    #[error("Lottery isn't drawn")]
+    NotDrawn,
+    /// Revealing a lottery whose sales aren't closed for a committed draw
+    #[error("Lottery isn't waiting for a reveal")]
+    NotAwaitingReveal,
+    #[error("Lottery isn't cancelled")]
+    NotCancelled,
+    /// Closing a ticket before every prize is paid
+    #[error("Lottery isn't settled")]
+    NotSettled,
+    #[error("Ticket doesn't hold the winning number")]
+    WrongWinner,
+    #[error("Fee account is wrong")]
+    WrongFeeAccount,
+    #[error("Lottery PDA is wrong")]
+    WrongPda,
+    /// Vault or buyer token account not matching the lottery
+    #[error("Token account is wrong")]
+    WrongTokenAccount,
+    /// Ticket not of this lottery, buyer or purchase index
+    #[error("Ticket is wrong")]
+    WrongTicket,
+    #[error("Buyer record is wrong")]
+    WrongBuyerRecord,
+    #[error("Config account is wrong")]
+    WrongConfig,
+    /// Signer isn't the lottery authority or config admin
+    #[error("Wrong authority")]
+    WrongAuthority,
+    #[error("Sysvar account is wrong")]
+    WrongSysvar,
+    #[error("Prize shares should add up to 10000 basis points")]
+    InvalidPrizeShares,
+    #[error("Prize tier doesn't exist")]
+    InvalidPrizeTier,
+    #[error("Prize tier already paid")]
+    PrizeAlreadyPaid,
+    /// Zero amount or price, or min over max
+    #[error("Invalid amount")]
+    InvalidAmount,
+    #[error("Over the per wallet ticket cap")]
+    OverWalletCap,
+    #[error("Fee is over the max fee")]
+    FeeTooHigh,
+    #[error("Seed does not match the commitment")]
+    CommitmentMismatch,
+    #[error("No slot hash after the sales closed yet")]
+    SlotHashNotAvailable,
+    #[error("Amount overflow")]
+    Overflow,
+    /// Writing to an account still in the layout from before versioning
+    #[error("Account has a legacy layout, run Migrate first")]
+    AccountNeedsMigration,
+    /// Refunding or closing a ticket twice
+    #[error("Ticket is already closed")]
+    TicketClosed,
+    /// Status change the lottery lifecycle doesn't allow
+    #[error("Invalid lottery status transition")]
+    InvalidStatusTransition,
+    /// End timestamp that isn't in the future
+    #[error("End timestamp has already passed")]
+    InvalidEndTimestamp,
+    /// Start slot or timestamp at or after the end
+    #[error("Lottery would open after it ends")]
+    InvalidStartTime,
+    /// Buying before the start slot or start timestamp
+    #[error("Lottery hasn't opened yet")]
+    LotteryNotStarted,
+    /// Starting a round while the previous one isn't settled
+    #[error("Previous round isn't settled yet")]
+    RoundNotSettled,
+    /// Round account that isn't the next or latest round of the series
+    #[error("Wrong round of the series")]
+    WrongRound,
+    /// Rollover on a lottery created without a rollover window
+    #[error("Rollover is off for this lottery")]
+    RolloverDisabled,
+    /// Rollover before the rollover window has passed or before every ticket is refunded
+    #[error("Winners or refunded buyers can still claim from the pot")]
+    PotStillClaimable,
+    /// Rollover into a lottery that isn't open, uses another mint or authority, or can't roll over itself
+    #[error("Lottery can't take the rollover")]
+    InvalidSuccessor,
+    /// UpdateLottery that would end the sales earlier or lower the cap below the tickets sold
+    #[error("Lottery can only be extended")]
+    LotteryShortened,
+    /// Native SOL payout too small to open an empty wallet, it has to hold lamports first
+    #[error("Payout is below the rent exempt minimum of an empty wallet")]
+    PayoutBelowRent,
+    /// Withdraw or Claim of a lottery nobody bought while it holds a rolled over jackpot,
+    /// or of an unsold series round whose vault isn't empty
+    #[error("Jackpot without a winner has to roll over")]
+    RolloverRequired,
+    /// Reveal after the reveal deadline, the lottery can only be cancelled by Draw
+    #[error("Reveal deadline has passed")]
+    RevealDeadlinePassed,
+    /// Crank reward on a lottery without a commitment, or above the ticket price
+    #[error("Crank reward needs a committed draw and can't exceed the ticket price")]
+    InvalidCrankReward,
 }
 
 impl From<LotteryError> for ProgramError {
@@ -19,3 +135,18 @@ impl From<LotteryError> for ProgramError {
         ProgramError::Custom(e as u32)
     }
 }
+
+impl<T> DecodeError<T> for LotteryError {
+    fn type_of() -> &'static str {
+        "LotteryError"
+    }
+}
+
+impl PrintProgramError for LotteryError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + DecodeFromPrimitive,
+    {
+        msg!(&self.to_string());
+    }
+}
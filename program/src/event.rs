@@ -0,0 +1,433 @@
+use solana_program::{log::sol_log_data, program_error::ProgramError, pubkey::Pubkey};
+use std::convert::TryInto;
+
+/// Only bumped if a field of an existing event changes, new events take a new tag
+pub const EVENT_SCHEMA_VERSION: u8 = 1;
+
+/// Events logged by the handlers as base64 "Program data:" lines
+/// Layout: schema version, tag, then every field little endian in declaration order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LotteryEvent {
+    /// tag 0
+    LotteryCreated {
+        lottery_id: Pubkey,
+        authority: Pubkey,
+        token_mint: Pubkey,
+        max_amount: u64,
+        ended_slot: u64,
+        ticket_price: u64,
+        ended_timestamp: i64,
+        start_slot: u64,
+        start_timestamp: i64,
+    },
+    /// tag 1
+    TicketBought {
+        lottery_id: Pubkey,
+        ticket_id: Pubkey,
+        buyer: Pubkey,
+        start_number: u64,
+        end_number: u64,
+        cost: u64,
+    },
+    /// tag 2, one winning number per prize tier prefixed by the tier count
+    Drawn {
+        lottery_id: Pubkey,
+        winning_numbers: Vec<u64>,
+    },
+    /// tag 3
+    PrizePaid {
+        lottery_id: Pubkey,
+        prize_index: u8,
+        winner: Pubkey,
+        amount: u64,
+        fee: u64,
+    },
+    /// tag 4, refunded is 0 unless the ticket was closed by Refund
+    TicketClosed {
+        lottery_id: Pubkey,
+        ticket_id: Pubkey,
+        buyer: Pubkey,
+        refunded: u64,
+    },
+    /// tag 5, the vault balance carried into the successor lottery
+    RolledOver {
+        lottery_id: Pubkey,
+        successor: Pubkey,
+        amount: u64,
+    },
+    /// tag 6, logged when the authority takes over, not when it is only proposed
+    AuthorityChanged {
+        lottery_id: Pubkey,
+        old_authority: Pubkey,
+        new_authority: Pubkey,
+    },
+    /// tag 7, the cap and end of an open lottery after UpdateLottery
+    LotteryUpdated {
+        lottery_id: Pubkey,
+        max_amount: u64,
+        ended_slot: u64,
+        ended_timestamp: i64,
+    },
+    /// tag 8, by Draw below the min amount or past the reveal deadline, buyers get a Refund
+    LotteryCancelled {
+        lottery_id: Pubkey,
+        sold: u64,
+    },
+    /// tag 9, sales of a committed lottery closed, the seed can be revealed in this slot range
+    AwaitingReveal {
+        lottery_id: Pubkey,
+        reveal_slot: u64,
+        reveal_deadline: u64,
+    },
+    /// tag 10, the crank reward paid out of the pot to whoever revealed
+    CrankRewardPaid {
+        lottery_id: Pubkey,
+        caller: Pubkey,
+        amount: u64,
+    },
+}
+
+impl LotteryEvent {
+    pub fn emit(&self) {
+        sol_log_data(&[&self.pack()]);
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = vec![EVENT_SCHEMA_VERSION];
+        match self {
+            Self::LotteryCreated {
+                lottery_id,
+                authority,
+                token_mint,
+                max_amount,
+                ended_slot,
+                ticket_price,
+                ended_timestamp,
+                start_slot,
+                start_timestamp,
+            } => {
+                data.push(0);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.extend_from_slice(&authority.to_bytes());
+                data.extend_from_slice(&token_mint.to_bytes());
+                data.extend_from_slice(&max_amount.to_le_bytes());
+                data.extend_from_slice(&ended_slot.to_le_bytes());
+                data.extend_from_slice(&ticket_price.to_le_bytes());
+                data.extend_from_slice(&ended_timestamp.to_le_bytes());
+                data.extend_from_slice(&start_slot.to_le_bytes());
+                data.extend_from_slice(&start_timestamp.to_le_bytes());
+            }
+            Self::TicketBought {
+                lottery_id,
+                ticket_id,
+                buyer,
+                start_number,
+                end_number,
+                cost,
+            } => {
+                data.push(1);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.extend_from_slice(&ticket_id.to_bytes());
+                data.extend_from_slice(&buyer.to_bytes());
+                data.extend_from_slice(&start_number.to_le_bytes());
+                data.extend_from_slice(&end_number.to_le_bytes());
+                data.extend_from_slice(&cost.to_le_bytes());
+            }
+            Self::Drawn {
+                lottery_id,
+                winning_numbers,
+            } => {
+                data.push(2);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.push(winning_numbers.len() as u8);
+                for number in winning_numbers {
+                    data.extend_from_slice(&number.to_le_bytes());
+                }
+            }
+            Self::PrizePaid {
+                lottery_id,
+                prize_index,
+                winner,
+                amount,
+                fee,
+            } => {
+                data.push(3);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.push(*prize_index);
+                data.extend_from_slice(&winner.to_bytes());
+                data.extend_from_slice(&amount.to_le_bytes());
+                data.extend_from_slice(&fee.to_le_bytes());
+            }
+            Self::TicketClosed {
+                lottery_id,
+                ticket_id,
+                buyer,
+                refunded,
+            } => {
+                data.push(4);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.extend_from_slice(&ticket_id.to_bytes());
+                data.extend_from_slice(&buyer.to_bytes());
+                data.extend_from_slice(&refunded.to_le_bytes());
+            }
+            Self::RolledOver {
+                lottery_id,
+                successor,
+                amount,
+            } => {
+                data.push(5);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.extend_from_slice(&successor.to_bytes());
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::AuthorityChanged {
+                lottery_id,
+                old_authority,
+                new_authority,
+            } => {
+                data.push(6);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.extend_from_slice(&old_authority.to_bytes());
+                data.extend_from_slice(&new_authority.to_bytes());
+            }
+            Self::LotteryUpdated {
+                lottery_id,
+                max_amount,
+                ended_slot,
+                ended_timestamp,
+            } => {
+                data.push(7);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.extend_from_slice(&max_amount.to_le_bytes());
+                data.extend_from_slice(&ended_slot.to_le_bytes());
+                data.extend_from_slice(&ended_timestamp.to_le_bytes());
+            }
+            Self::LotteryCancelled { lottery_id, sold } => {
+                data.push(8);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.extend_from_slice(&sold.to_le_bytes());
+            }
+            Self::AwaitingReveal {
+                lottery_id,
+                reveal_slot,
+                reveal_deadline,
+            } => {
+                data.push(9);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.extend_from_slice(&reveal_slot.to_le_bytes());
+                data.extend_from_slice(&reveal_deadline.to_le_bytes());
+            }
+            Self::CrankRewardPaid {
+                lottery_id,
+                caller,
+                amount,
+            } => {
+                data.push(10);
+                data.extend_from_slice(&lottery_id.to_bytes());
+                data.extend_from_slice(&caller.to_bytes());
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    /// Decodes an event of the current schema, anything left after its fields is rejected
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (version, rest) = input.split_first().ok_or(ProgramError::InvalidArgument)?;
+        if *version != EVENT_SCHEMA_VERSION {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (tag, rest) = rest.split_first().ok_or(ProgramError::InvalidArgument)?;
+        let (event, rest) = match tag {
+            0 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (authority, rest) = Self::unpack_pubkey(rest)?;
+                let (token_mint, rest) = Self::unpack_pubkey(rest)?;
+                let (max_amount, rest) = Self::unpack_u64(rest)?;
+                let (ended_slot, rest) = Self::unpack_u64(rest)?;
+                let (ticket_price, rest) = Self::unpack_u64(rest)?;
+                let (ended_timestamp, rest) = Self::unpack_u64(rest)?;
+                let (start_slot, rest) = Self::unpack_u64(rest)?;
+                let (start_timestamp, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::LotteryCreated {
+                        lottery_id,
+                        authority,
+                        token_mint,
+                        max_amount,
+                        ended_slot,
+                        ticket_price,
+                        ended_timestamp: ended_timestamp as i64,
+                        start_slot,
+                        start_timestamp: start_timestamp as i64,
+                    },
+                    rest,
+                )
+            }
+            1 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (ticket_id, rest) = Self::unpack_pubkey(rest)?;
+                let (buyer, rest) = Self::unpack_pubkey(rest)?;
+                let (start_number, rest) = Self::unpack_u64(rest)?;
+                let (end_number, rest) = Self::unpack_u64(rest)?;
+                let (cost, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::TicketBought {
+                        lottery_id,
+                        ticket_id,
+                        buyer,
+                        start_number,
+                        end_number,
+                        cost,
+                    },
+                    rest,
+                )
+            }
+            2 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (count, mut rest) = rest.split_first().ok_or(ProgramError::InvalidArgument)?;
+                let mut winning_numbers = vec![];
+                for _ in 0..*count {
+                    let (number, next) = Self::unpack_u64(rest)?;
+                    winning_numbers.push(number);
+                    rest = next;
+                }
+                (
+                    Self::Drawn {
+                        lottery_id,
+                        winning_numbers,
+                    },
+                    rest,
+                )
+            }
+            3 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (prize_index, rest) = rest.split_first().ok_or(ProgramError::InvalidArgument)?;
+                let (winner, rest) = Self::unpack_pubkey(rest)?;
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (fee, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::PrizePaid {
+                        lottery_id,
+                        prize_index: *prize_index,
+                        winner,
+                        amount,
+                        fee,
+                    },
+                    rest,
+                )
+            }
+            4 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (ticket_id, rest) = Self::unpack_pubkey(rest)?;
+                let (buyer, rest) = Self::unpack_pubkey(rest)?;
+                let (refunded, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::TicketClosed {
+                        lottery_id,
+                        ticket_id,
+                        buyer,
+                        refunded,
+                    },
+                    rest,
+                )
+            }
+            5 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (successor, rest) = Self::unpack_pubkey(rest)?;
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::RolledOver {
+                        lottery_id,
+                        successor,
+                        amount,
+                    },
+                    rest,
+                )
+            }
+            6 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (old_authority, rest) = Self::unpack_pubkey(rest)?;
+                let (new_authority, rest) = Self::unpack_pubkey(rest)?;
+                (
+                    Self::AuthorityChanged {
+                        lottery_id,
+                        old_authority,
+                        new_authority,
+                    },
+                    rest,
+                )
+            }
+            7 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (max_amount, rest) = Self::unpack_u64(rest)?;
+                let (ended_slot, rest) = Self::unpack_u64(rest)?;
+                let (ended_timestamp, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::LotteryUpdated {
+                        lottery_id,
+                        max_amount,
+                        ended_slot,
+                        ended_timestamp: ended_timestamp as i64,
+                    },
+                    rest,
+                )
+            }
+            8 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (sold, rest) = Self::unpack_u64(rest)?;
+                (Self::LotteryCancelled { lottery_id, sold }, rest)
+            }
+            9 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (reveal_slot, rest) = Self::unpack_u64(rest)?;
+                let (reveal_deadline, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::AwaitingReveal {
+                        lottery_id,
+                        reveal_slot,
+                        reveal_deadline,
+                    },
+                    rest,
+                )
+            }
+            10 => {
+                let (lottery_id, rest) = Self::unpack_pubkey(rest)?;
+                let (caller, rest) = Self::unpack_pubkey(rest)?;
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::CrankRewardPaid {
+                        lottery_id,
+                        caller,
+                        amount,
+                    },
+                    rest,
+                )
+            }
+            _ => return Err(ProgramError::InvalidArgument),
+        };
+        if !rest.is_empty() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(event)
+    }
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        if input.len() < 8 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (amount, rest) = input.split_at(8);
+        let amount = amount
+            .try_into()
+            .ok()
+            .map(u64::from_le_bytes)
+            .ok_or(ProgramError::InvalidArgument)?;
+        Ok((amount, rest))
+    }
+    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+        if input.len() < 32 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (key, rest) = input.split_at(32);
+        Ok((Pubkey::new(key), rest))
+    }
+}
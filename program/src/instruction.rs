@@ -1,11 +1,25 @@
-use crate::error::LotteryError::InvalidInstruction;
-use solana_program::{ program_error::ProgramError};
-use std::convert::TryInto;
+use borsh::{BorshDeserialize, BorshSerialize};
+use crate::{
+    error::LotteryError::InvalidInstruction, find_buyer_record_address, find_config_address,
+    find_lottery_address, find_round_address, find_ticket_address, get_token_account_address,
+    token_program_id,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{clock, rent, slot_hashes},
+};
+use spl_associated_token_account::get_associated_token_address;
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub enum LotteryMachineInstructions {
+    /// A native SOL lottery passes the system program as token mint and token program, its PDA
+    /// as token reciever and pays fees and prizes to wallets instead of ATAs
     /// 0.`[writable,signer]` lottery id
     /// 1.`[writable,signer]` lottery authority
     /// 2.`[]` fee authority
-    /// 3.`[writable]` lottery PDA
+    /// 3.`[writable]` lottery PDA (PDA of "lottery", lottery id)
     /// 4.`[writable]` token reciever (ATA owned by lottery PDA, Derived from mint,lottery PDA) 
     /// 5.`[writable]` fee reciever (ATA owned by fee authority)
     /// 6.`[]` Associated Token Program
@@ -14,26 +28,43 @@ pub enum LotteryMachineInstructions {
     /// 9.`[]` system program
     /// 10.`[]` Sysvar Clock
     /// 11.`[]` Sysvar Rent
+    /// 12.`[]` program config
     InitLottery {
         max_amount: u64,
         slot: u64, //how mant slot this Lottery last
+        commitment: [u8; 32], //hash of the draw seed for commit-reveal, all zero to draw without it
+        prize_shares: Vec<u16>, //share of the pot per prize tier in basis points, empty for one winner, u32 length prefixed
+        min_amount: u64, //cancel and refund if less than this is sold, 0 to always draw
+        ticket_price: u64, //token amount per ticket, max and min amount count tickets
+        max_per_wallet: u64, //most tickets one buyer can hold, 0 for no cap
+        crank_reward: u64, //token amount paid from the pot to whoever calls Reveal, needs a commitment and at most the ticket price
+        end_timestamp: i64, //unix time the sales end at, replaces the slot duration, 0 to end by slot
+        start_slot: u64, //no sales before this slot, the slot duration counts from it, 0 to open now
+        start_timestamp: i64, //no sales before this unix time, 0 to open now
+        rollover_window: i64, //seconds winners have to claim before Rollover can move the pot on, 0 to turn rollover off
     },
     /// 0.`[writable]` lottery id
-    /// 1.`[writable,signer]` ticket id
+    /// 1.`[writable]` ticket id (PDA of "ticket", lottery id, buyer authority, buyer record ticket count)
     /// 2.`[writable,signer]` buyer authority
     /// 3.`[writable]` token reciever (ATA owned by lottery PDA, Derived from mint,lottery PDA)
-    /// 4.`[writable]` buyer token account
+    /// 4.`[writable]` buyer token account, unused for a native SOL lottery
     /// 5.`[]` token program
     /// 6.`[]` Sysvar: Clock
     /// 7.`[]` system program
     /// 8.`[]` Sysvar Rent
+    /// 9.`[writable]` buyer record (PDA of "buyer", lottery id, buyer authority)
     Buy {
-        amount: u64, // amount to participate
+        amount: u64, // number of tickets to buy, costs amount * ticket price
+        partial: bool, //buy whatever is left if fewer than amount remain, LotterySoldOut otherwise
     },
+    /// Callable by anyone once the lottery is sold out or past its ended slot or end timestamp
     /// 0.`[writable]` lottery id
-    /// 1.`[signer]` lottery authority
+    /// 1.`[signer]` caller
     /// 2.`[]` Sysvar: Clock
     /// 3.`[]` Sysvar: Slot Hashes
+    /// For a committed lottery this only closes the sales, the number is picked by Reveal
+    /// and the crank reward goes to its caller. Past the reveal deadline Draw cancels it
+    /// A lottery that sold less than its min amount is cancelled instead
     Draw {},
     /// 0.`[writable]` lottery id
     /// 1.`[writable,signer]` lottery authority
@@ -48,46 +79,698 @@ pub enum LotteryMachineInstructions {
     /// 10.`[]` Sysvar Rent
     /// 11.`[]` Associated Token Program
     /// 12.`[]` Winner account
-    Withdraw {},
+    /// 13.`[]` program config
+    /// Pays one prize tier, the vault is closed once every tier has been paid
+    Withdraw {
+        prize_index: u8,
+    },
 
     Close {},
+    /// Callable by anyone holding the seed, from the reveal slot (closed slot + REVEAL_SLOT_OFFSET)
+    /// until the reveal deadline REVEAL_WINDOW slots later
+    /// 0.`[writable]` lottery id
+    /// 1.`[signer]` caller
+    /// 2.`[]` Sysvar: Clock
+    /// 3.`[]` Sysvar: Slot Hashes
+    /// Only when the lottery pays a crank reward:
+    /// 4.`[writable]` token reciever (ATA owned by lottery PDA, Derived from mint,lottery PDA)
+    /// 5.`[writable]` caller token account
+    /// 6.`[]` lottery PDA
+    /// 7.`[]` token program
+    Reveal {
+        seed: [u8; 32], //preimage of the commitment stored at InitLottery
+    },
+    /// 0.`[writable]` lottery id
+    /// 1.`[writable]` ticket id
+    /// 2.`[writable,signer]` buyer authority
+    /// 3.`[writable]` buyer token account
+    /// 4.`[writable]` token reciever (ATA owned by lottery PDA, Derived from mint,lottery PDA)
+    /// 5.`[]` lottery PDA
+    /// 6.`[]` token program
+    /// 7.`[writable]` lottery authority
+    Refund {},
+    /// 0.`[writable]` program config (PDA of "config")
+    /// 1.`[writable,signer]` config admin
+    /// 2.`[]` fee reciever (wallet owning the fee ATAs)
+    /// 3.`[]` system program
+    /// 4.`[]` Sysvar Rent
+    InitConfig {
+        fee_bps: u16,     //protocol fee in basis points of each prize
+        max_fee_bps: u16, //fee_bps can never be raised above this
+    },
+    /// 0.`[writable]` program config
+    /// 1.`[signer]` config admin
+    /// 2.`[]` new config admin
+    /// 3.`[]` new fee reciever
+    SetConfig {
+        fee_bps: u16,
+        max_fee_bps: u16, //can only be lowered
+    },
+    /// Same accounts as Withdraw with the winner and the authority swapped
+    /// 0.`[writable]` lottery id
+    /// 1.`[writable,signer]` winner (buyer of the winning ticket)
+    /// 2.`[writable]` token reciever (ATA owned by lottery PDA, Derived from mint,lottery PDA)
+    /// 3.`[writable]` fee reciever (ATA owned by fee authority)
+    /// 4.`[writable]` winner token account (ATA of the winner, created if missing)
+    /// 5.`[]` winning ticket id
+    /// 6.`[]` lottery PDA
+    /// 7.`[]` token mint
+    /// 8.`[]` token program
+    /// 9.`[]` system program
+    /// 10.`[]` Sysvar Rent
+    /// 11.`[]` Associated Token Program
+    /// 12.`[writable]` lottery authority
+    /// 13.`[]` program config
+    Claim {
+        prize_index: u8,
+    },
+    /// Rewrites a legacy lottery or ticket account into the current versioned layout
+    /// 0.`[writable]` lottery id or ticket id to migrate
+    /// 1.`[writable,signer]` payer of the extra rent
+    /// 2.`[]` system program
+    /// 3.`[]` Sysvar Rent
+    /// Only for a lottery whose vault isn't closed yet, the vault moves to the new lottery PDA
+    /// 4.`[writable]` lottery authority, gets the rent of the legacy vault back
+    /// 5.`[writable]` legacy token reciever
+    /// 6.`[]` legacy PDA (program address of the lottery id alone)
+    /// 7.`[writable]` token reciever (ATA owned by lottery PDA, Derived from mint,lottery PDA)
+    /// 8.`[]` lottery PDA
+    /// 9.`[]` token mint
+    /// 10.`[]` token program
+    /// 11.`[]` Associated Token Program
+    Migrate {},
+    /// Creates a series of recurring lotteries, rounds are started by NextRound
+    /// 0.`[writable,signer]` series id
+    /// 1.`[writable,signer]` series authority, authority of every round
+    /// 2.`[]` fee authority
+    /// 3.`[]` vault PDA (PDA of "lottery", series id)
+    /// 4.`[writable]` token reciever (ATA owned by vault PDA, Derived from mint,vault PDA)
+    /// 5.`[writable]` fee reciever (ATA owned by fee authority)
+    /// 6.`[]` Associated Token Program
+    /// 7.`[]` token mint
+    /// 8.`[]` token program
+    /// 9.`[]` system program
+    /// 10.`[]` Sysvar Rent
+    /// 11.`[]` program config
+    InitSeries {
+        max_amount: u64,
+        slot: u64, //how many slots every round lasts
+        prize_shares: Vec<u16>,
+        min_amount: u64,
+        ticket_price: u64,
+        max_per_wallet: u64,
+        crank_reward: u64, //has to be 0, rounds are drawn without a commitment
+        duration: i64, //how many seconds every round lasts, replaces slot, 0 to last by slot
+        rollover_window: i64, //rollover window of every round, 0 to turn rollover off
+    },
+    /// Callable by anyone once the latest round is settled, opens the next one on the series vault
+    /// 0.`[writable]` series id
+    /// 1.`[writable]` round lottery id (PDA of "round", series id, round index)
+    /// 2.`[]` previous round lottery id, the series id for the first round
+    /// 3.`[writable,signer]` payer of the round rent
+    /// 4.`[]` system program
+    /// 5.`[]` Sysvar Clock
+    /// 6.`[]` Sysvar Rent
+    NextRound {},
+    /// Moves the vault balance of a lottery into the vault of its successor, once it is drawn with
+    /// nothing sold or its rollover window has passed, or it is cancelled and every ticket refunded.
+    /// The successor of a series round is the next round, which shares the vault.
+    /// An unsold or cancelled series round with a pot rolls over even without a rollover window
+    /// 0.`[writable]` lottery id
+    /// 1.`[writable,signer]` lottery authority
+    /// 2.`[writable]` token reciever (ATA owned by vault PDA)
+    /// 3.`[]` vault PDA (PDA of "lottery", lottery id or series id of a round)
+    /// 4.`[writable]` successor lottery id, the series id for a series round
+    /// 5.`[writable]` successor token reciever, the same vault for a series round
+    /// 6.`[]` token program
+    /// 7.`[]` Sysvar Clock
+    Rollover {},
+    /// Hands the lottery to a new authority, or only proposes it until it signs AcceptAuthority
+    /// 0.`[writable]` lottery id
+    /// 1.`[signer]` lottery authority
+    /// 2.`[]` new authority, the default pubkey with propose set withdraws a pending proposal
+    SetAuthority {
+        propose: bool, //false to hand over at once
+    },
+    /// 0.`[writable]` lottery id
+    /// 1.`[signer]` pending authority proposed by SetAuthority
+    AcceptAuthority {},
+    /// Extends an open lottery before its sales end, the end can only move back and the max amount
+    /// can't go below the tickets sold, reaching them sells the lottery out.
+    /// 0 keeps the current value, a lottery ending by slot can't get an end timestamp
+    /// 0.`[writable]` lottery id
+    /// 1.`[signer]` lottery authority
+    /// 2.`[]` Sysvar Clock
+    UpdateLottery {
+        max_amount: u64,
+        ended_slot: u64,
+        ended_timestamp: i64,
+    },
 }
 impl LotteryMachineInstructions {
+    /// Borsh decoding, short input, unknown tags and trailing bytes are all InvalidInstruction
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
-
-        Ok(match tag {
-            //First byte in data:
-            //0:Initialize lottery
-            //1:Buy
-            0 => {
-                let (max, rest) = Self::unpack_u64(rest).unwrap();
-                let slot = Self::unpack_u64(rest).unwrap().0;
-                //let message = format(format_args!("slot_ended: {:?}, max", slot));
-                //msg!(&message);
-                Self::InitLottery {
-                    max_amount: max,
-                    slot: slot,
-                }
-            }
-            1 => {
-                let amount = Self::unpack_u64(rest).unwrap().0;
-                Self::Buy { amount: amount }
-            }
-            2 => Self::Draw {},
-            3 => Self::Withdraw {},
-            4 => Self::Close {},
-            _ => return Err(InvalidInstruction.into()),
-        })
-    }
-    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
-        let (amount, rest) = input.split_at(8);
-        let amount = amount
-            .try_into()
-            .ok()
-            .map(u64::from_le_bytes)
-            .ok_or(InvalidInstruction)?;
-        Ok((amount, rest))
-    }
-    
+        Self::try_from_slice(input).map_err(|_| InvalidInstruction.into())
+    }
+    pub fn pack(&self) -> Vec<u8> {
+        self.try_to_vec().unwrap()
+    }
+}
+
+/// Creates an InitLottery instruction, the lottery vault and fee ATA are derived from the mint
+#[allow(clippy::too_many_arguments)]
+pub fn init_lottery(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    authority: &Pubkey,
+    fee_receiver: &Pubkey,
+    token_mint: &Pubkey,
+    max_amount: u64,
+    slot: u64,
+    commitment: [u8; 32],
+    prize_shares: Vec<u16>,
+    min_amount: u64,
+    ticket_price: u64,
+    max_per_wallet: u64,
+    crank_reward: u64,
+    end_timestamp: i64,
+    start_slot: u64,
+    start_timestamp: i64,
+    rollover_window: i64,
+) -> Instruction {
+    let lottery_pda = find_lottery_address(lottery_id, program_id).0;
+    let data = LotteryMachineInstructions::InitLottery {
+        max_amount,
+        slot,
+        commitment,
+        prize_shares,
+        min_amount,
+        ticket_price,
+        max_per_wallet,
+        crank_reward,
+        end_timestamp,
+        start_slot,
+        start_timestamp,
+        rollover_window,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*lottery_id, true),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(*fee_receiver, false),
+        AccountMeta::new_readonly(lottery_pda, false),
+        AccountMeta::new(get_token_account_address(&lottery_pda, token_mint), false),
+        AccountMeta::new(get_token_account_address(fee_receiver, token_mint), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(*token_mint, false),
+        AccountMeta::new_readonly(token_program_id(token_mint), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(clock::id(), false),
+        AccountMeta::new_readonly(rent::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a Buy instruction, `ticket_index` is the ticket count of the buyer record
+/// (0 for the first purchase). A native SOL lottery is paid from the buyer wallet
+#[allow(clippy::too_many_arguments)]
+pub fn buy(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    buyer: &Pubkey,
+    buyer_token_account: &Pubkey,
+    token_reciever: &Pubkey,
+    token_mint: &Pubkey,
+    ticket_index: u64,
+    amount: u64,
+    partial: bool,
+) -> Instruction {
+    let ticket_id = find_ticket_address(lottery_id, buyer, ticket_index, program_id).0;
+    let accounts = vec![
+        AccountMeta::new(*lottery_id, false),
+        AccountMeta::new(ticket_id, false),
+        AccountMeta::new(*buyer, true),
+        AccountMeta::new(*token_reciever, false),
+        AccountMeta::new(*buyer_token_account, false),
+        AccountMeta::new_readonly(token_program_id(token_mint), false),
+        AccountMeta::new_readonly(clock::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(rent::id(), false),
+        AccountMeta::new(find_buyer_record_address(lottery_id, buyer, program_id).0, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::Buy { amount, partial }.pack(),
+    }
+}
+
+/// Creates a Draw instruction
+pub fn draw(program_id: &Pubkey, lottery_id: &Pubkey, caller: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*lottery_id, false),
+        AccountMeta::new(*caller, true),
+        AccountMeta::new_readonly(clock::id(), false),
+        AccountMeta::new_readonly(slot_hashes::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::Draw {}.pack(),
+    }
+}
+
+/// Creates a Reveal instruction, `crank_reward` is (vault id, token reciever, caller token account,
+/// token mint) for a lottery that pays whoever reveals it
+pub fn reveal(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    caller: &Pubkey,
+    seed: [u8; 32],
+    crank_reward: Option<(&Pubkey, &Pubkey, &Pubkey, &Pubkey)>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*lottery_id, false),
+        AccountMeta::new_readonly(*caller, true),
+        AccountMeta::new_readonly(clock::id(), false),
+        AccountMeta::new_readonly(slot_hashes::id(), false),
+    ];
+    if let Some((vault_id, token_reciever, caller_token_account, token_mint)) = crank_reward {
+        accounts.push(AccountMeta::new(*token_reciever, false));
+        accounts.push(AccountMeta::new(*caller_token_account, false));
+        accounts.push(AccountMeta::new_readonly(
+            find_lottery_address(vault_id, program_id).0,
+            false,
+        ));
+        accounts.push(AccountMeta::new_readonly(token_program_id(token_mint), false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::Reveal { seed }.pack(),
+    }
+}
+
+/// Creates a Withdraw instruction paying `prize_index` to the buyer of `winning_ticket`,
+/// `vault_id` is the lottery id or the series id of a round (see `Lottery::vault_id`)
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    vault_id: &Pubkey,
+    authority: &Pubkey,
+    token_reciever: &Pubkey,
+    fee_reciever: &Pubkey,
+    winner: &Pubkey,
+    winning_ticket: &Pubkey,
+    token_mint: &Pubkey,
+    prize_index: u8,
+) -> Instruction {
+    let mut accounts = payout_accounts(
+        program_id,
+        lottery_id,
+        vault_id,
+        authority,
+        token_reciever,
+        fee_reciever,
+        winner,
+        winning_ticket,
+        token_mint,
+    );
+    accounts.insert(12, AccountMeta::new_readonly(*winner, false));
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::Withdraw { prize_index }.pack(),
+    }
+}
+
+/// Creates a Claim instruction, signed by the buyer of `winning_ticket`
+#[allow(clippy::too_many_arguments)]
+pub fn claim(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    vault_id: &Pubkey,
+    winner: &Pubkey,
+    authority: &Pubkey,
+    token_reciever: &Pubkey,
+    fee_reciever: &Pubkey,
+    winning_ticket: &Pubkey,
+    token_mint: &Pubkey,
+    prize_index: u8,
+) -> Instruction {
+    let mut accounts = payout_accounts(
+        program_id,
+        lottery_id,
+        vault_id,
+        winner,
+        token_reciever,
+        fee_reciever,
+        winner,
+        winning_ticket,
+        token_mint,
+    );
+    accounts.insert(12, AccountMeta::new(*authority, false));
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::Claim { prize_index }.pack(),
+    }
+}
+
+/// Accounts of Withdraw and Claim but the one at index 12
+#[allow(clippy::too_many_arguments)]
+fn payout_accounts(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    vault_id: &Pubkey,
+    signer: &Pubkey,
+    token_reciever: &Pubkey,
+    fee_reciever: &Pubkey,
+    winner: &Pubkey,
+    winning_ticket: &Pubkey,
+    token_mint: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*lottery_id, false),
+        AccountMeta::new(*signer, true),
+        AccountMeta::new(*token_reciever, false),
+        AccountMeta::new(*fee_reciever, false),
+        AccountMeta::new(get_token_account_address(winner, token_mint), false),
+        AccountMeta::new_readonly(*winning_ticket, false),
+        AccountMeta::new_readonly(find_lottery_address(vault_id, program_id).0, false),
+        AccountMeta::new_readonly(*token_mint, false),
+        AccountMeta::new_readonly(token_program_id(token_mint), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(rent::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ]
+}
+
+/// Creates a Close instruction, the ticket rent goes back to its buyer
+pub fn close(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    ticket_id: &Pubkey,
+    buyer: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*lottery_id, false),
+        AccountMeta::new(*ticket_id, false),
+        AccountMeta::new(*buyer, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::Close {}.pack(),
+    }
+}
+
+/// Creates a Refund instruction for one ticket of a cancelled lottery
+#[allow(clippy::too_many_arguments)]
+pub fn refund(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    vault_id: &Pubkey,
+    ticket_id: &Pubkey,
+    buyer: &Pubkey,
+    buyer_token_account: &Pubkey,
+    token_reciever: &Pubkey,
+    token_mint: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*lottery_id, false),
+        AccountMeta::new(*ticket_id, false),
+        AccountMeta::new(*buyer, true),
+        AccountMeta::new(*buyer_token_account, false),
+        AccountMeta::new(*token_reciever, false),
+        AccountMeta::new_readonly(find_lottery_address(vault_id, program_id).0, false),
+        AccountMeta::new_readonly(token_program_id(token_mint), false),
+        AccountMeta::new(*authority, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::Refund {}.pack(),
+    }
+}
+
+/// Creates an InitConfig instruction
+pub fn init_config(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    fee_receiver: &Pubkey,
+    fee_bps: u16,
+    max_fee_bps: u16,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(find_config_address(program_id).0, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new_readonly(*fee_receiver, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(rent::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::InitConfig {
+            fee_bps,
+            max_fee_bps,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a SetConfig instruction
+pub fn set_config(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+    fee_receiver: &Pubkey,
+    fee_bps: u16,
+    max_fee_bps: u16,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(find_config_address(program_id).0, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(*new_admin, false),
+        AccountMeta::new_readonly(*fee_receiver, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::SetConfig {
+            fee_bps,
+            max_fee_bps,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a Migrate instruction, `legacy_vault` is (lottery authority, legacy token reciever,
+/// token mint) for a lottery whose vault isn't closed yet. Fails if `account` has no legacy
+/// lottery PDA
+pub fn migrate(
+    program_id: &Pubkey,
+    account: &Pubkey,
+    payer: &Pubkey,
+    legacy_vault: Option<(&Pubkey, &Pubkey, &Pubkey)>,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*account, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(rent::id(), false),
+    ];
+    if let Some((authority, legacy_token_reciever, token_mint)) = legacy_vault {
+        // legacy lottery ids were picked so this address has no bump seed
+        let legacy_pda = Pubkey::create_program_address(&[&account.to_bytes()], program_id)?;
+        let lottery_pda = find_lottery_address(account, program_id).0;
+        accounts.push(AccountMeta::new(*authority, false));
+        accounts.push(AccountMeta::new(*legacy_token_reciever, false));
+        accounts.push(AccountMeta::new_readonly(legacy_pda, false));
+        accounts.push(AccountMeta::new(
+            get_associated_token_address(&lottery_pda, token_mint),
+            false,
+        ));
+        accounts.push(AccountMeta::new_readonly(lottery_pda, false));
+        accounts.push(AccountMeta::new_readonly(*token_mint, false));
+        accounts.push(AccountMeta::new_readonly(token_program_id(token_mint), false));
+        accounts.push(AccountMeta::new_readonly(spl_associated_token_account::id(), false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::Migrate {}.pack(),
+    })
+}
+
+/// Creates an InitSeries instruction, the shared vault and fee ATA are derived from the mint
+#[allow(clippy::too_many_arguments)]
+pub fn init_series(
+    program_id: &Pubkey,
+    series_id: &Pubkey,
+    authority: &Pubkey,
+    fee_receiver: &Pubkey,
+    token_mint: &Pubkey,
+    max_amount: u64,
+    slot: u64,
+    prize_shares: Vec<u16>,
+    min_amount: u64,
+    ticket_price: u64,
+    max_per_wallet: u64,
+    crank_reward: u64,
+    duration: i64,
+    rollover_window: i64,
+) -> Instruction {
+    let vault_pda = find_lottery_address(series_id, program_id).0;
+    let data = LotteryMachineInstructions::InitSeries {
+        max_amount,
+        slot,
+        prize_shares,
+        min_amount,
+        ticket_price,
+        max_per_wallet,
+        crank_reward,
+        duration,
+        rollover_window,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*series_id, true),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(*fee_receiver, false),
+        AccountMeta::new_readonly(vault_pda, false),
+        AccountMeta::new(get_token_account_address(&vault_pda, token_mint), false),
+        AccountMeta::new(get_token_account_address(fee_receiver, token_mint), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(*token_mint, false),
+        AccountMeta::new_readonly(token_program_id(token_mint), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(rent::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a NextRound instruction opening round `round` (the round count of the series)
+pub fn next_round(program_id: &Pubkey, series_id: &Pubkey, payer: &Pubkey, round: u64) -> Instruction {
+    let previous_round = if round == 0 {
+        *series_id
+    } else {
+        find_round_address(series_id, round - 1, program_id).0
+    };
+    let accounts = vec![
+        AccountMeta::new(*series_id, false),
+        AccountMeta::new(find_round_address(series_id, round, program_id).0, false),
+        AccountMeta::new_readonly(previous_round, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(clock::id(), false),
+        AccountMeta::new_readonly(rent::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::NextRound {}.pack(),
+    }
+}
+
+/// Creates a Rollover instruction, a series round passes its series id and its own vault as the successor
+#[allow(clippy::too_many_arguments)]
+pub fn rollover(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    vault_id: &Pubkey,
+    authority: &Pubkey,
+    token_reciever: &Pubkey,
+    token_mint: &Pubkey,
+    successor_id: &Pubkey,
+    successor_token_reciever: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*lottery_id, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(*token_reciever, false),
+        AccountMeta::new_readonly(find_lottery_address(vault_id, program_id).0, false),
+        AccountMeta::new(*successor_id, false),
+        AccountMeta::new(*successor_token_reciever, false),
+        AccountMeta::new_readonly(token_program_id(token_mint), false),
+        AccountMeta::new_readonly(clock::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::Rollover {}.pack(),
+    }
+}
+
+/// Creates a SetAuthority instruction
+pub fn set_authority(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    authority: &Pubkey,
+    new_authority: &Pubkey,
+    propose: bool,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*lottery_id, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new_readonly(*new_authority, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::SetAuthority { propose }.pack(),
+    }
+}
+
+/// Creates an AcceptAuthority instruction signed by the pending authority
+pub fn accept_authority(program_id: &Pubkey, lottery_id: &Pubkey, pending_authority: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*lottery_id, false),
+        AccountMeta::new_readonly(*pending_authority, true),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::AcceptAuthority {}.pack(),
+    }
+}
+
+/// Creates an UpdateLottery instruction
+pub fn update_lottery(
+    program_id: &Pubkey,
+    lottery_id: &Pubkey,
+    authority: &Pubkey,
+    max_amount: u64,
+    ended_slot: u64,
+    ended_timestamp: i64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*lottery_id, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new_readonly(clock::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: LotteryMachineInstructions::UpdateLottery {
+            max_amount,
+            ended_slot,
+            ended_timestamp,
+        }
+        .pack(),
+    }
 }
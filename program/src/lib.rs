@@ -1,10 +1,12 @@
 pub mod error;
+pub mod event;
 pub mod instruction;
 pub mod processor;
 pub mod state;
-use std::str::FromStr;
-
-use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey,msg};
+use solana_program::{
+    entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey, system_program,
+};
+use state::{Config, BUYER_RECORD_SEED, CONFIG_SEED, LOTTERY_SEED, ROUND_SEED, TICKET_SEED};
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 
@@ -16,10 +18,72 @@ pub fn check_program_account(program_account: &Pubkey) -> ProgramResult {
     }
     Ok(())
 }
-pub fn check_fee_account(fee_reciever_id: &Pubkey) -> ProgramResult{
-    if fee_reciever_id.clone() != Pubkey::from_str("2wnEcArzCpX1QRdtpHRXxZ7k9b1UeK16mPt26LPWFZ6V").unwrap(){
-        msg!("Fee reciever is wrong");
-        return Err(ProgramError::InvalidAccountData);
+/// Address of the program config PDA holding the protocol fee settings
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+/// Address of the PDA owning the vault of `lottery_id`, or of every round of a series
+pub fn find_lottery_address(lottery_id: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LOTTERY_SEED, &lottery_id.to_bytes()], program_id)
+}
+/// Address of the record tracking how many tickets `buyer` holds in a lottery
+pub fn find_buyer_record_address(
+    lottery_id: &Pubkey,
+    buyer: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[BUYER_RECORD_SEED, &lottery_id.to_bytes(), &buyer.to_bytes()],
+        program_id,
+    )
+}
+/// Address of the ticket `buyer` got from their `index`th Buy in a lottery
+pub fn find_ticket_address(
+    lottery_id: &Pubkey,
+    buyer: &Pubkey,
+    index: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            TICKET_SEED,
+            &lottery_id.to_bytes(),
+            &buyer.to_bytes(),
+            &index.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+/// Address of the lottery account of round `round` of a series
+pub fn find_round_address(series_id: &Pubkey, round: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ROUND_SEED, &series_id.to_bytes(), &round.to_le_bytes()],
+        program_id,
+    )
+}
+/// Native SOL lotteries take the system program id as their mint
+pub fn is_native_mint(token_mint: &Pubkey) -> bool {
+    *token_mint == system_program::id()
+}
+/// Account `owner` is paid to, its ATA of `token_mint` or the wallet itself for native SOL
+pub fn get_token_account_address(owner: &Pubkey, token_mint: &Pubkey) -> Pubkey {
+    if is_native_mint(token_mint) {
+        *owner
+    } else {
+        spl_associated_token_account::get_associated_token_address(owner, token_mint)
+    }
+}
+/// Program moving the funds of a lottery, the system program for native SOL
+pub fn token_program_id(token_mint: &Pubkey) -> Pubkey {
+    if is_native_mint(token_mint) {
+        system_program::id()
+    } else {
+        spl_token::id()
+    }
+}
+pub fn check_fee_account(config: &Config, fee_reciever_id: &Pubkey) -> ProgramResult{
+    if *fee_reciever_id != config.fee_receiver {
+        return Err(error::LotteryError::WrongFeeAccount.into());
     }
 
     Ok(())
@@ -1,9 +1,17 @@
 use std::convert::TryInto;
 
 use crate::{
-    check_fee_account, check_program_account,
+    check_fee_account, check_program_account, token_program_id,
+    error::LotteryError,
+    event::LotteryEvent,
+    find_buyer_record_address, find_config_address, find_lottery_address, find_round_address,
+    find_ticket_address, is_native_mint,
     instruction::LotteryMachineInstructions,
-    state::{Lottery, Ticket},
+    state::{
+        AccountType, BuyerRecord, Config, Lottery, LotteryStatus, Series, Ticket, TicketStatus,
+        BUYER_RECORD_SEED, CONFIG_SEED, FEE_BPS_TOTAL, LEGACY_LOTTERY_LEN, LEGACY_TICKET_LEN, LOTTERY_SEED, LOTTERY_VERSION, MAX_PRIZES,
+        PRIZE_SHARE_TOTAL, ROUND_SEED, TICKET_SEED, TICKET_VERSION,
+    },
 };
 use solana_program::clock;
 use solana_program::rent::Rent;
@@ -16,7 +24,8 @@ use solana_program::{
     program_pack::Pack,
     pubkey::Pubkey,
     system_instruction::{self},
-    sysvar::Sysvar,
+    system_program,
+    sysvar::{slot_hashes, Sysvar},
 };
 use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
 use spl_token::instruction as TokenIns;
@@ -33,33 +42,158 @@ impl Processor {
         let instruction = LotteryMachineInstructions::unpack(instruction_data)?;
         check_program_account(program_id)?;
         match instruction {
-            LotteryMachineInstructions::InitLottery { max_amount, slot } => {
+            LotteryMachineInstructions::InitLottery {
+                max_amount,
+                slot,
+                commitment,
+                prize_shares,
+                min_amount,
+                ticket_price,
+                max_per_wallet,
+                crank_reward,
+                end_timestamp,
+                start_slot,
+                start_timestamp,
+                rollover_window,
+            } => {
                 msg!("Instruction: Init Lottrey");
-                Self::process_init_lottery(accounts, max_amount, slot, program_id)
+                Self::process_init_lottery(
+                    accounts,
+                    max_amount,
+                    slot,
+                    commitment,
+                    prize_shares,
+                    min_amount,
+                    ticket_price,
+                    max_per_wallet,
+                    crank_reward,
+                    end_timestamp,
+                    start_slot,
+                    start_timestamp,
+                    rollover_window,
+                    program_id,
+                )
             }
-            LotteryMachineInstructions::Buy { amount } => {
+            LotteryMachineInstructions::Buy { amount, partial } => {
                 msg!("Instruction: Buy");
-                Self::process_buy(accounts, amount, program_id)
+                Self::process_buy(accounts, amount, partial, program_id)
             }
             LotteryMachineInstructions::Draw {} => {
                 msg!("Instruction: Draw");
                 Self::process_draw(accounts, program_id)
             }
-            LotteryMachineInstructions::Withdraw {} => {
+            LotteryMachineInstructions::Withdraw { prize_index } => {
                 msg!("Instruction: Withdraw");
-                Self::process_withdraw(accounts, program_id)
+                Self::process_payout(accounts, prize_index, false, program_id)
             }
             LotteryMachineInstructions::Close {} => {
                 msg!("Instruction: Close");
                 Self::process_close_ticket(accounts, program_id)
             }
+            LotteryMachineInstructions::Reveal { seed } => {
+                msg!("Instruction: Reveal");
+                Self::process_reveal(accounts, seed, program_id)
+            }
+            LotteryMachineInstructions::Refund {} => {
+                msg!("Instruction: Refund");
+                Self::process_refund(accounts, program_id)
+            }
+            LotteryMachineInstructions::InitConfig {
+                fee_bps,
+                max_fee_bps,
+            } => {
+                msg!("Instruction: Init Config");
+                Self::process_init_config(accounts, fee_bps, max_fee_bps, program_id)
+            }
+            LotteryMachineInstructions::SetConfig {
+                fee_bps,
+                max_fee_bps,
+            } => {
+                msg!("Instruction: Set Config");
+                Self::process_set_config(accounts, fee_bps, max_fee_bps, program_id)
+            }
+            LotteryMachineInstructions::Claim { prize_index } => {
+                msg!("Instruction: Claim");
+                Self::process_payout(accounts, prize_index, true, program_id)
+            }
+            LotteryMachineInstructions::Migrate {} => {
+                msg!("Instruction: Migrate");
+                Self::process_migrate(accounts, program_id)
+            }
+            LotteryMachineInstructions::InitSeries {
+                max_amount,
+                slot,
+                prize_shares,
+                min_amount,
+                ticket_price,
+                max_per_wallet,
+                crank_reward,
+                duration,
+                rollover_window,
+            } => {
+                msg!("Instruction: Init Series");
+                Self::process_init_series(
+                    accounts,
+                    max_amount,
+                    slot,
+                    prize_shares,
+                    min_amount,
+                    ticket_price,
+                    max_per_wallet,
+                    crank_reward,
+                    duration,
+                    rollover_window,
+                    program_id,
+                )
+            }
+            LotteryMachineInstructions::NextRound {} => {
+                msg!("Instruction: Next Round");
+                Self::process_next_round(accounts, program_id)
+            }
+            LotteryMachineInstructions::Rollover {} => {
+                msg!("Instruction: Rollover");
+                Self::process_rollover(accounts, program_id)
+            }
+            LotteryMachineInstructions::SetAuthority { propose } => {
+                msg!("Instruction: Set Authority");
+                Self::process_set_authority(accounts, propose, program_id)
+            }
+            LotteryMachineInstructions::AcceptAuthority {} => {
+                msg!("Instruction: Accept Authority");
+                Self::process_accept_authority(accounts, program_id)
+            }
+            LotteryMachineInstructions::UpdateLottery {
+                max_amount,
+                ended_slot,
+                ended_timestamp,
+            } => {
+                msg!("Instruction: Update Lottery");
+                Self::process_update_lottery(
+                    accounts,
+                    max_amount,
+                    ended_slot,
+                    ended_timestamp,
+                    program_id,
+                )
+            }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn process_init_lottery(
         accounts: &[AccountInfo],
         max_amount: u64,
         slot: u64,
+        commitment: [u8; 32],
+        prize_shares: Vec<u16>,
+        min_amount: u64,
+        ticket_price: u64,
+        max_per_wallet: u64,
+        crank_reward: u64,
+        end_timestamp: i64,
+        start_slot: u64,
+        start_timestamp: i64,
+        rollover_window: i64,
         program_id: &Pubkey,
     ) -> ProgramResult {
         msg!("init lottery process");
@@ -76,14 +210,18 @@ impl Processor {
         let system_program_account = next_account_info(account_info_iter)?;
         let clock_account = next_account_info(account_info_iter)?;
         let rent = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
 
         //msg!("account all unpacked");
 
         let writable_accounts = vec![lottery_id, authority, lottery_ata, fee_ata];
         if Self::check_writable(writable_accounts) {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(LotteryError::AccountNotWritable.into());
         }
         //msg!("writable_accounts cheked");
+        if !authority.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
         let rent_info = Rent::from_account_info(rent)?;
         let create_inx = system_instruction::create_account(
             authority.key,
@@ -97,93 +235,118 @@ impl Processor {
         invoke(&create_inx, &[lottery_id.clone(), authority.clone()])?;
         let mut lottery_info = Lottery::unpack_unchecked(&lottery_id.data.borrow())?;
         check_program_account(lottery_id.owner)?;
-        if lottery_info.account_type != 0 {
+        if lottery_info.account_type != AccountType::Uninitialized {
             return Err(ProgramError::AccountAlreadyInitialized);
         }
-        if !authority.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
         //msg!("All account type is good");
-        let pda =
-            Pubkey::create_program_address(&[&lottery_id.key.to_bytes().clone()], program_id)?;
-        if lottery_pda.key.clone() != pda {
-            return Err(ProgramError::InvalidAccountData);
+        let (pda, bump) = find_lottery_address(lottery_id.key, program_id);
+        if *lottery_pda.key != pda {
+            return Err(LotteryError::WrongPda.into());
         }
         //msg!("PDA created");
-        check_fee_account(fee_authority.key)?;
-
-        if get_associated_token_address(fee_authority.key, token_mint.key) != fee_ata.key.clone() {
-            return Err(ProgramError::InvalidAccountData);
-        }
-        //msg!("fee_ata is right");
-        if fee_ata.owner != token_program.key {
-            let fee_ata_ix = create_associated_token_account(
-                authority.key,
-                &fee_authority.key.clone(),
-                token_mint.key,
-            );
-            invoke(
-                &fee_ata_ix,
-                &[
-                    authority.clone(),
-                    fee_authority.clone(),
-                    fee_ata.clone(),
-                    system_program_account.clone(),
-                    rent.clone(),
-                    token_program.clone(),
-                    token_mint.clone(),
-                ],
-            )?;
-        }
-
-        if lottery_ata.owner != token_program.key {
-            let lottery_ata_ix =
-                create_associated_token_account(authority.key, lottery_pda.key, token_mint.key);
-            invoke(
-                &lottery_ata_ix,
-                &[
-                    authority.clone(),
-                    lottery_pda.clone(),
-                    lottery_ata.clone(),
-                    system_program_account.clone(),
-                    rent.clone(),
-                    token_program.clone(),
-                    token_mint.clone(),
-                ],
-            )?;
-        }
-
-        let fee_token_account = TokenAccount::unpack(&fee_ata.data.borrow())?;
+        Self::init_token_accounts(
+            authority,
+            fee_authority,
+            lottery_pda,
+            lottery_ata,
+            fee_ata,
+            token_mint,
+            token_program,
+            system_program_account,
+            rent,
+            config_account,
+            program_id,
+        )?;
 
-        if fee_token_account.owner != fee_authority.key.clone() {
-            return Err(ProgramError::InvalidAccountData);
+        let prize_shares = Self::check_lottery_params(
+            prize_shares,
+            max_amount,
+            min_amount,
+            ticket_price,
+            crank_reward,
+            commitment != [0; 32],
+        )?;
+        if rollover_window < 0 {
+            return Err(LotteryError::InvalidAmount.into());
         }
 
         let clock_info = clock::Clock::from_account_info(clock_account)?;
 
-        let slot_ended = clock_info.slot.checked_add(slot).unwrap();
+        let slot_ended = if end_timestamp != 0 {
+            if end_timestamp <= clock_info.unix_timestamp {
+                return Err(LotteryError::InvalidEndTimestamp.into());
+            }
+            // only the timestamp ends the sales
+            u64::MAX
+        } else {
+            std::cmp::max(clock_info.slot, start_slot)
+                .checked_add(slot)
+                .ok_or(LotteryError::Overflow)?
+        };
+        if (start_slot != 0 && start_slot >= slot_ended)
+            || (start_timestamp != 0 && end_timestamp != 0 && start_timestamp >= end_timestamp)
+        {
+            return Err(LotteryError::InvalidStartTime.into());
+        }
 
         //msg!("writing data to lottery info");
-        lottery_info.account_type = 1;
-        lottery_info.authority = authority.key.clone();
+        lottery_info.account_type = AccountType::Lottery;
+        lottery_info.set_status(LotteryStatus::Open)?;
+        lottery_info.authority = *authority.key;
         lottery_info.lottery_number = 0;
         lottery_info.ended_slot = slot_ended;
+        lottery_info.ended_timestamp = end_timestamp;
+        lottery_info.start_slot = start_slot;
+        lottery_info.start_timestamp = start_timestamp;
         lottery_info.max_amount = max_amount;
-        lottery_info.token_reciever = lottery_ata.key.clone();
-        lottery_info.fee_reciever = fee_ata.key.clone();
+        lottery_info.token_reciever = *lottery_ata.key;
+        lottery_info.fee_reciever = *fee_ata.key;
         lottery_info.current_amount = 0;
-        lottery_info.token_mint = token_mint.key.clone();
+        lottery_info.token_mint = *token_mint.key;
+        lottery_info.commitment = commitment;
+        lottery_info.closed_slot = 0;
+        lottery_info.prize_count = prize_shares.len() as u8;
+        lottery_info.prize_shares = [0; MAX_PRIZES];
+        lottery_info.prize_shares[..prize_shares.len()].copy_from_slice(&prize_shares);
+        lottery_info.winning_numbers = [0; MAX_PRIZES];
+        lottery_info.paid_prizes = 0;
+        lottery_info.prize_pool = 0;
+        lottery_info.min_amount = min_amount;
+        lottery_info.refunded_amount = 0;
+        lottery_info.ticket_price = ticket_price;
+        lottery_info.max_per_wallet = max_per_wallet;
+        lottery_info.crank_reward = crank_reward;
+        lottery_info.bump = bump;
+        lottery_info.version = LOTTERY_VERSION;
+        lottery_info.rollover_window = rollover_window;
+        LotteryEvent::LotteryCreated {
+            lottery_id: *lottery_id.key,
+            authority: lottery_info.authority,
+            token_mint: lottery_info.token_mint,
+            max_amount,
+            ended_slot: slot_ended,
+            ticket_price,
+            ended_timestamp: end_timestamp,
+            start_slot,
+            start_timestamp,
+        }
+        .emit();
         Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
         msg!(&*format!("Lottery initialized, id: {:?}", lottery_id.key));
         msg!(&*format!(
-            "Max amount: {:?}, ended slot: {:?}",
-            max_amount, slot_ended
+            "Max amount: {:?}, ended slot: {:?}, ended timestamp: {:?}, ticket price: {:?}",
+            max_amount, slot_ended, end_timestamp, ticket_price
         ));
 
         Ok(())
     }
 
-    fn process_buy(accounts: &[AccountInfo], amount: u64, program_id: &Pubkey) -> ProgramResult {
+    fn process_buy(
+        accounts: &[AccountInfo],
+        amount: u64,
+        partial: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
         let lottery_id = next_account_info(account_info_iter)?;
@@ -193,9 +356,10 @@ impl Processor {
         let buyer_token_account = next_account_info(account_info_iter)?;
         let token_program = next_account_info(account_info_iter)?;
         let clock_account = next_account_info(account_info_iter)?;
-        let _system_program_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
 
         let rent = next_account_info(account_info_iter)?;
+        let buyer_record = next_account_info(account_info_iter)?;
 
         //msg!("accounts all unpacked");
         let writable_accounts = vec![
@@ -204,65 +368,185 @@ impl Processor {
             buy_authority,
             lottery_ata,
             buyer_token_account,
+            buyer_record,
         ];
 
         if Self::check_writable(writable_accounts) {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(LotteryError::AccountNotWritable.into());
         }
 
-        if ticket_id.data_is_empty() {
+        let (record_key, record_bump) =
+            find_buyer_record_address(lottery_id.key, buy_authority.key, program_id);
+        if record_key != *buyer_record.key {
+            return Err(LotteryError::WrongBuyerRecord.into());
+        }
+        if buyer_record.data_is_empty() {
             let rent_info = Rent::from_account_info(rent)?;
             let create_inx = system_instruction::create_account(
                 buy_authority.key,
-                ticket_id.key,
-                rent_info.minimum_balance(Ticket::LEN),
-                Ticket::LEN.try_into().unwrap(),
+                buyer_record.key,
+                rent_info.minimum_balance(BuyerRecord::LEN),
+                BuyerRecord::LEN.try_into().unwrap(),
                 program_id,
             );
-            //msg!("Create Lottery accounts");
+            invoke_signed(
+                &create_inx,
+                &[
+                    buy_authority.clone(),
+                    buyer_record.clone(),
+                    system_program_account.clone(),
+                ],
+                &[&[
+                    BUYER_RECORD_SEED,
+                    &lottery_id.key.to_bytes(),
+                    &buy_authority.key.to_bytes(),
+                    &[record_bump],
+                ]],
+            )?;
+            let record_info = BuyerRecord {
+                account_type: AccountType::BuyerRecord,
+                lottery_id: *lottery_id.key,
+                buyer: *buy_authority.key,
+                purchased_amount: 0,
+                bump: record_bump,
+                ticket_count: 0,
+            };
+            BuyerRecord::pack(record_info, &mut buyer_record.data.borrow_mut())?;
+        }
+        check_program_account(buyer_record.owner)?;
+        let mut record_info = BuyerRecord::unpack(&buyer_record.data.borrow())?;
 
-            invoke(&create_inx, &[ticket_id.clone(), buy_authority.clone()])?;
+        let (ticket_key, ticket_bump) = find_ticket_address(
+            lottery_id.key,
+            buy_authority.key,
+            record_info.ticket_count,
+            program_id,
+        );
+        if ticket_key != *ticket_id.key {
+            msg!(&*format!(
+                "Wrong ticket address, expected index {:?}",
+                record_info.ticket_count
+            ));
+            return Err(LotteryError::WrongTicket.into());
+        }
+        if !ticket_id.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
         }
+        let rent_info = Rent::from_account_info(rent)?;
+        let create_inx = system_instruction::create_account(
+            buy_authority.key,
+            ticket_id.key,
+            rent_info.minimum_balance(Ticket::LEN),
+            Ticket::LEN.try_into().unwrap(),
+            program_id,
+        );
+        invoke_signed(
+            &create_inx,
+            &[
+                buy_authority.clone(),
+                ticket_id.clone(),
+                system_program_account.clone(),
+            ],
+            &[&[
+                TICKET_SEED,
+                &lottery_id.key.to_bytes(),
+                &buy_authority.key.to_bytes(),
+                &record_info.ticket_count.to_le_bytes(),
+                &[ticket_bump],
+            ]],
+        )?;
         check_program_account(lottery_id.owner)?;
         check_program_account(ticket_id.owner)?;
         // msg!("writable accounts cheked");
 
-        let mut lottery_info = Lottery::unpack_unchecked(&lottery_id.data.borrow())?;
+        let mut lottery_info = Self::unpack_lottery(lottery_id)?;
 
         let mut ticket_info = Ticket::unpack_unchecked(&ticket_id.data.borrow())?;
         let clock = clock::Clock::from_account_info(clock_account)?;
 
-        if lottery_info.ended_slot < clock.slot.clone() {
-            msg!("This Lottery ends");
-            return Err(ProgramError::InvalidAccountData);
+        if lottery_info.status == LotteryStatus::SoldOut {
+            return Err(LotteryError::LotterySoldOut.into());
+        } else if lottery_info.status != LotteryStatus::Open {
+            return Err(LotteryError::LotteryNotOpen.into());
         }
-        if lottery_info.current_amount > lottery_info.max_amount {
-            msg!("This Lottery is full");
-            return Err(ProgramError::InvalidAccountData);
+        if !lottery_info.has_started(&clock) {
+            return Err(LotteryError::LotteryNotStarted.into());
+        }
+        if lottery_info.has_ended(&clock) {
+            return Err(LotteryError::LotteryEnded.into());
         }
 
-        ticket_info.account_type = 2;
+        ticket_info.account_type = AccountType::Ticket;
+        ticket_info.status = TicketStatus::Active;
+        ticket_info.version = TICKET_VERSION;
         if buy_authority.is_signer {
-            ticket_info.buyer = buy_authority.key.clone();
+            ticket_info.buyer = *buy_authority.key;
         } else {
-            msg!("Buyer isn't signer");
-            return Err(ProgramError::InvalidAccountData);
+            return Err(ProgramError::MissingRequiredSignature);
         }
-        if amount <= 0 {
-            msg!("amount should be over 0");
-            return Err(ProgramError::InvalidArgument);
+        if amount == 0 {
+            return Err(LotteryError::InvalidAmount.into());
         }
+        // lotteries oversold before the cap was enforced have nothing left either
+        let remaining = lottery_info
+            .max_amount
+            .saturating_sub(lottery_info.current_amount);
+        let amount = if amount <= remaining {
+            amount
+        } else if partial && remaining != 0 {
+            msg!(&*format!("only {:?} tickets left, buying those", remaining));
+            remaining
+        } else {
+            msg!(&*format!("only {:?} tickets left", remaining));
+            return Err(LotteryError::LotterySoldOut.into());
+        };
 
-        ticket_info.lottery_id = lottery_id.key.clone();
-        ticket_info.start_number = lottery_info.current_amount.clone().checked_add(1).unwrap();
-        if lottery_ata.key.clone() == lottery_info.token_reciever {
+        ticket_info.lottery_id = *lottery_id.key;
+        ticket_info.start_number = lottery_info
+            .current_amount
+            .checked_add(1)
+            .ok_or(LotteryError::Overflow)?;
+        let cost = amount
+            .checked_mul(lottery_info.ticket_price)
+            .ok_or(LotteryError::Overflow)?;
+        record_info.purchased_amount = record_info
+            .purchased_amount
+            .checked_add(amount)
+            .ok_or(LotteryError::Overflow)?;
+        if lottery_info.max_per_wallet != 0
+            && record_info.purchased_amount > lottery_info.max_per_wallet
+        {
+            msg!(&*format!(
+                "Over the per wallet cap of {:?} tickets",
+                lottery_info.max_per_wallet
+            ));
+            return Err(LotteryError::OverWalletCap.into());
+        }
+        record_info.ticket_count = record_info
+            .ticket_count
+            .checked_add(1)
+            .ok_or(LotteryError::Overflow)?;
+        if *lottery_ata.key != lottery_info.token_reciever {
+            return Err(LotteryError::WrongTokenAccount.into());
+        }
+        Self::check_token_program(&lottery_info, token_program)?;
+        if lottery_info.is_native() {
+            invoke(
+                &system_instruction::transfer(buy_authority.key, lottery_ata.key, cost),
+                &[
+                    buy_authority.clone(),
+                    lottery_ata.clone(),
+                    system_program_account.clone(),
+                ],
+            )?;
+        } else {
             let transfer_ix = TokenIns::transfer(
                 &token_program.key.clone(),
                 &buyer_token_account.key.clone(),
                 &lottery_info.token_reciever.clone(),
                 &buy_authority.key.clone(),
                 &[],
-                amount,
+                cost,
             )?;
             invoke(
                 &transfer_ix,
@@ -274,16 +558,31 @@ impl Processor {
             )?;
         }
 
-        let end_unmber = amount.checked_add(lottery_info.current_amount).unwrap();
-        lottery_info.current_amount = end_unmber.clone();
-        ticket_info.end_number = end_unmber.clone();
+        let end_unmber = amount
+            .checked_add(lottery_info.current_amount)
+            .ok_or(LotteryError::Overflow)?;
+        lottery_info.current_amount = end_unmber;
+        ticket_info.end_number = end_unmber;
+        if lottery_info.current_amount == lottery_info.max_amount {
+            lottery_info.set_status(LotteryStatus::SoldOut)?;
+            msg!("lottery sold out");
+        }
         msg!(&*format!(
-            "your ticket number start from {:?} to {:?}",
-            lottery_info.current_amount.clone(),
-            end_unmber
+            "your ticket number start from {:?} to {:?}, paid {:?}",
+            ticket_info.start_number, end_unmber, cost
         ));
+        LotteryEvent::TicketBought {
+            lottery_id: *lottery_id.key,
+            ticket_id: *ticket_id.key,
+            buyer: ticket_info.buyer,
+            start_number: ticket_info.start_number,
+            end_number: end_unmber,
+            cost,
+        }
+        .emit();
         Ticket::pack(ticket_info, &mut ticket_id.data.borrow_mut())?;
         Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+        BuyerRecord::pack(record_info, &mut buyer_record.data.borrow_mut())?;
 
         Ok(())
     }
@@ -291,19 +590,41 @@ impl Processor {
     fn process_draw(accounts: &[AccountInfo], _program_id: &Pubkey) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let lottery_id = next_account_info(account_info_iter)?;
-        let authority = next_account_info(account_info_iter)?;
+        let caller = next_account_info(account_info_iter)?;
         let clock_account = next_account_info(account_info_iter)?;
         let slot_hash_account = next_account_info(account_info_iter)?;
         msg!("unpack lottery");
-        let mut lottery_info = Lottery::unpack(&lottery_id.data.borrow())?;
+        let mut lottery_info = Self::unpack_lottery(lottery_id)?;
         let clock = clock::Clock::from_account_info(clock_account)?;
-        if lottery_info.account_type == 3 {
-            msg!("Wrong account type, already drawed ");
-            return Err(ProgramError::InvalidArgument);
-        } else if lottery_info.account_type != 1 {
-            msg!("Wrong account type ");
-            return Err(ProgramError::InvalidArgument);
+        if !caller.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if lottery_info.status == LotteryStatus::AwaitingReveal
+            && lottery_info.reveal_deadline() < clock.slot
+        {
+            // the seed was never revealed, buyers get their tickets refunded
+            check_program_account(lottery_id.owner)?;
+            lottery_info.set_status(LotteryStatus::Cancelled)?;
+            msg!(&*format!(
+                "lottery cancelled, not revealed by slot {:?}",
+                lottery_info.reveal_deadline()
+            ));
+            LotteryEvent::LotteryCancelled {
+                lottery_id: *lottery_id.key,
+                sold: lottery_info.current_amount,
+            }
+            .emit();
+            Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+            return Ok(());
+        }
+        if lottery_info.status == LotteryStatus::Drawn {
+            return Err(LotteryError::AlreadyDrawn.into());
+        } else if lottery_info.status != LotteryStatus::Open
+            && lottery_info.status != LotteryStatus::SoldOut
+        {
+            return Err(LotteryError::LotteryNotOpen.into());
         }
+        let (_, slot_hash) = Self::latest_slot_hash(slot_hash_account)?;
 
         let mut random_data: Vec<u8> = vec![];
         random_data.extend_from_slice(&clock.slot.to_le_bytes());
@@ -312,47 +633,138 @@ impl Processor {
         random_data.extend_from_slice(&clock.epoch_start_timestamp.to_le_bytes());
         random_data.extend_from_slice(&lottery_info.current_amount.to_le_bytes());
         random_data.extend_from_slice(&lottery_id.key.to_bytes());
-        random_data.extend_from_slice(&slot_hash_account.data.borrow());
+        random_data.extend_from_slice(&slot_hash);
 
-        msg!(&*format!("hashdata: {:?}", hash::hash(&random_data)));
-        msg!("hashing number");
-        let random_number_hash: [u8; 8] = hash::hash(&random_data).to_bytes()[0..8]
-            .try_into()
-            .unwrap();
-        let mut random_number = 0;
-        if lottery_info.current_amount != 0 {
-            random_number = u64::from_le_bytes(random_number_hash) % lottery_info.current_amount;
-            if random_number == 0 {
-                random_number = lottery_info.current_amount;
+        check_program_account(lottery_id.owner)?;
+        if !lottery_id.is_writable {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        // anyone can crank an ended lottery, the authority isn't needed
+        if lottery_info.current_amount >= lottery_info.max_amount
+            || lottery_info.has_ended(&clock)
+        {
+            if lottery_info.current_amount != 0
+                && lottery_info.current_amount < lottery_info.min_amount
+            {
+                lottery_info.set_status(LotteryStatus::Cancelled)?;
+                msg!(&*format!(
+                    "lottery cancelled, sold {:?} of min {:?}",
+                    lottery_info.current_amount, lottery_info.min_amount
+                ));
+                LotteryEvent::LotteryCancelled {
+                    lottery_id: *lottery_id.key,
+                    sold: lottery_info.current_amount,
+                }
+                .emit();
+                Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+                return Ok(());
+            }
+            if lottery_info.commitment != [0; 32] {
+                // the number is picked from the hash of a slot after this one, and the crank
+                // reward goes to whoever reveals it
+                lottery_info.closed_slot = clock.slot;
+                lottery_info.set_status(LotteryStatus::AwaitingReveal)?;
+                msg!(&*format!(
+                    "sales closed at slot: {:?}, reveal from slot {:?} to {:?}",
+                    clock.slot,
+                    lottery_info.reveal_slot(),
+                    lottery_info.reveal_deadline()
+                ));
+                LotteryEvent::AwaitingReveal {
+                    lottery_id: *lottery_id.key,
+                    reveal_slot: lottery_info.reveal_slot(),
+                    reveal_deadline: lottery_info.reveal_deadline(),
+                }
+                .emit();
+                Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+                return Ok(());
             }
+            Self::draw_winning_numbers(&mut lottery_info, &random_data);
+            lottery_info.set_status(LotteryStatus::Drawn)?;
+            lottery_info.drawn_timestamp = clock.unix_timestamp;
+            msg!(&*format!(
+                "winner numbers: {:?}",
+                &lottery_info.winning_numbers[..lottery_info.prize_count as usize]
+            ));
+            Self::emit_drawn(&lottery_info, lottery_id.key);
+            Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+        } else {
+            return Err(LotteryError::LotteryNotEnded.into());
         }
 
+        Ok(())
+    }
+
+    fn process_reveal(accounts: &[AccountInfo], seed: [u8; 32], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lottery_id = next_account_info(account_info_iter)?;
+        let caller = next_account_info(account_info_iter)?;
+        let clock_account = next_account_info(account_info_iter)?;
+        let slot_hash_account = next_account_info(account_info_iter)?;
+        let mut lottery_info = Self::unpack_lottery(lottery_id)?;
+        let clock = clock::Clock::from_account_info(clock_account)?;
         check_program_account(lottery_id.owner)?;
-        if !authority.is_signer && lottery_info.authority != authority.key.clone() {
-            msg!("Not authority");
+        if !lottery_id.is_writable {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+
+        if lottery_info.status != LotteryStatus::AwaitingReveal {
+            return Err(LotteryError::NotAwaitingReveal.into());
+        }
+        // anyone holding the seed can reveal it, the commitment is what binds the authority
+        if !caller.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
-        if (lottery_info.current_amount >= lottery_info.max_amount
-            || lottery_info.ended_slot < clock.slot)
-            && lottery_info.account_type == 1
-        {
-            lottery_info.lottery_number = random_number;
-            lottery_info.account_type = 3;
-            msg!(&*format!("winner number: {:?}", random_number));
-            Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
-        } else {
-            msg!("lottery not ended");
-            return Err(ProgramError::InvalidArgument);
+        if lottery_info.reveal_deadline() < clock.slot {
+            return Err(LotteryError::RevealDeadlinePassed.into());
+        }
+        if hash::hash(&seed).to_bytes() != lottery_info.commitment {
+            return Err(LotteryError::CommitmentMismatch.into());
+        }
+        let (hash_slot, slot_hash) =
+            Self::slot_hash_from(slot_hash_account, lottery_info.reveal_slot())?;
+        if lottery_info.crank_reward != 0 {
+            Self::pay_crank_reward(
+                &lottery_info,
+                lottery_id,
+                caller,
+                account_info_iter,
+                program_id,
+            )?;
         }
 
+        let mut random_data: Vec<u8> = vec![];
+        random_data.extend_from_slice(&seed);
+        random_data.extend_from_slice(&slot_hash);
+        random_data.extend_from_slice(&lottery_info.current_amount.to_le_bytes());
+        random_data.extend_from_slice(&lottery_id.key.to_bytes());
+
+        Self::draw_winning_numbers(&mut lottery_info, &random_data);
+        lottery_info.set_status(LotteryStatus::Drawn)?;
+        lottery_info.drawn_timestamp = clock.unix_timestamp;
+        msg!(&*format!(
+            "winner numbers: {:?}, slot hash from slot: {:?}",
+            &lottery_info.winning_numbers[..lottery_info.prize_count as usize],
+            hash_slot
+        ));
+        Self::emit_drawn(&lottery_info, lottery_id.key);
+        Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+
         Ok(())
     }
 
-    fn process_withdraw(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    /// Withdraw and Claim share one account layout, only the signer at index 1
+    /// and the account at index 12 swap between lottery authority and winner
+    fn process_payout(
+        accounts: &[AccountInfo],
+        prize_index: u8,
+        by_winner: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
         let lottery_id = next_account_info(account_info_iter)?;
-        let lottery_authority = next_account_info(account_info_iter)?;
+        let signer = next_account_info(account_info_iter)?;
         let lottery_ata = next_account_info(account_info_iter)?;
         let fee_ata = next_account_info(account_info_iter)?;
         let winner_ata = next_account_info(account_info_iter)?;
@@ -363,56 +775,66 @@ impl Processor {
         let system_program_account = next_account_info(account_info_iter)?;
         let rent = next_account_info(account_info_iter)?;
         let _ata_program = next_account_info(account_info_iter)?;
-        let winner_account = next_account_info(account_info_iter)?;
-        let mut lottery_info = Lottery::unpack(&lottery_id.data.borrow())?;
+        let other_party = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let (lottery_authority, winner_account) = if by_winner {
+            (other_party, signer)
+        } else {
+            (signer, other_party)
+        };
+        let mut lottery_info = Self::unpack_lottery(lottery_id)?;
+        let config_info = Self::unpack_config(config_account, program_id)?;
 
-        if lottery_info.fee_reciever != fee_ata.key.clone() {
-            msg!("wrong fee account");
-            return Err(ProgramError::InvalidAccountData);
+        if lottery_info.fee_reciever != *fee_ata.key {
+            return Err(LotteryError::WrongFeeAccount.into());
         }
-
-        if lottery_info.account_type != 3 {
-            msg!("Lottery haven't ended");
-            return Err(ProgramError::InvalidAccountData);
+        // the pot of a native SOL vault is read from this account's lamports
+        if lottery_info.token_reciever != *lottery_ata.key {
+            return Err(LotteryError::WrongTokenAccount.into());
         }
-        if lottery_info.authority != lottery_authority.key.clone() || !(lottery_authority.is_signer)
-        {
-            msg!("wrong authority");
-            return Err(ProgramError::InvalidAccountData);
+
+        if lottery_info.status != LotteryStatus::Drawn {
+            return Err(LotteryError::NotDrawn.into());
         }
-        let pda =
-            Pubkey::create_program_address(&[&lottery_id.key.to_bytes().clone()], program_id)?;
-        if pda != lottery_pda.key.clone() {
-            msg!("PDA is wrong");
-            return Err(ProgramError::InvalidAccountData);
+        if lottery_info.authority != *lottery_authority.key || !(signer.is_signer) {
+            return Err(LotteryError::WrongAuthority.into());
         }
+        Self::check_lottery_pda(&lottery_info, lottery_id, lottery_pda, program_id)?;
+        Self::check_token_program(&lottery_info, token_program)?;
         check_program_account(lottery_id.owner)?;
+        let vault_id = lottery_info.vault_id(lottery_id.key);
 
+        let prize_index = prize_index as usize;
         if lottery_info.current_amount != 0 {
+            if prize_index >= lottery_info.prize_count as usize {
+                return Err(LotteryError::InvalidPrizeTier.into());
+            }
+            if lottery_info.paid_prizes & (1 << prize_index) != 0 {
+                return Err(LotteryError::PrizeAlreadyPaid.into());
+            }
             let writable_accounts = vec![
                 lottery_id,
-                lottery_authority,
+                signer,
                 lottery_ata,
                 fee_ata,
                 winner_ata,
             ];
             if Self::check_writable(writable_accounts) {
-                return Err(ProgramError::InvalidAccountData);
+                return Err(LotteryError::AccountNotWritable.into());
             }
 
-            
             check_program_account(winning_ticket.owner)?;
 
-            if winner_ata.owner != token_program.key {
+            if !lottery_info.is_native() && winner_ata.owner != token_program.key {
                 let lottery_ata_ix = create_associated_token_account(
-                    lottery_authority.key,
+                    signer.key,
                     winner_account.key,
                     token_mint.key,
                 );
                 invoke(
                     &lottery_ata_ix,
                     &[
-                        lottery_authority.clone(),
+                        signer.clone(),
                         lottery_pda.clone(),
                         winner_ata.clone(),
                         winner_account.clone(),
@@ -424,80 +846,107 @@ impl Processor {
                 )?;
             }
 
-            let ticket_info = Ticket::unpack(&winning_ticket.data.borrow())?;
+            let ticket_info = Ticket::unpack_versioned(&winning_ticket.data.borrow())?;
+            let winning_number = lottery_info.winning_numbers[prize_index];
 
-            if lottery_info.lottery_number <= ticket_info.end_number
-                && lottery_info.lottery_number >= ticket_info.start_number
+            if ticket_info.lottery_id == *lottery_id.key
+                && winning_number <= ticket_info.end_number
+                && winning_number >= ticket_info.start_number
             {
                 msg!("Winner correct")
             } else {
-                msg!("Winner is wrong");
-                return Err(ProgramError::InvalidAccountData);
+                return Err(LotteryError::WrongWinner.into());
             }
-            let winner_ata_info = TokenAccount::unpack(&winner_ata.data.borrow())?;
+            // a native SOL prize goes to the winner wallet itself
+            let winner_owner = if lottery_info.is_native() {
+                *winner_ata.key
+            } else {
+                TokenAccount::unpack(&winner_ata.data.borrow())?.owner
+            };
 
-            if ticket_info.buyer != winner_account.key.clone()
-                && ticket_info.buyer != winner_ata_info.owner
-            {
-                msg!("Winner is wrong");
-                return Err(ProgramError::InvalidAccountData);
+            // the prize can only go to an account of the buyer, whoever signs the payout
+            if ticket_info.buyer != *winner_account.key || winner_owner != ticket_info.buyer {
+                return Err(LotteryError::WrongWinner.into());
             }
-            let lottery_ata_info = TokenAccount::unpack(&lottery_ata.data.borrow())?;
+            let vault_amount = Self::vault_balance(&lottery_info, lottery_ata)?;
 
-            let fee_amount = lottery_ata_info.amount * 10 / 100;
-            let prize_amount = lottery_ata_info.amount - fee_amount;
+            if lottery_info.paid_prizes == 0 {
+                lottery_info.prize_pool = vault_amount;
+            }
+            let tier_amount = lottery_info.prize_amount(prize_index, vault_amount);
 
-            let transfer_fee_ix = TokenIns::transfer(
-                token_program.key,
-                lottery_ata.key,
-                fee_ata.key,
-                &pda,
-                &[],
-                fee_amount,
-            )
-            .unwrap();
+            let fee_amount = (tier_amount as u128 * config_info.fee_bps as u128
+                / FEE_BPS_TOTAL as u128) as u64;
+            let prize_amount = tier_amount - fee_amount;
 
-            let transfer_prize_ix = TokenIns::transfer(
-                token_program.key,
-                lottery_ata.key,
-                winner_ata.key,
-                &pda,
-                &[],
+            Self::transfer_from_vault(
+                token_program,
+                lottery_ata,
+                fee_ata,
+                lottery_pda,
+                &vault_id,
+                lottery_info.bump,
+                fee_amount,
+            )?;
+            Self::transfer_from_vault(
+                token_program,
+                lottery_ata,
+                winner_ata,
+                lottery_pda,
+                &vault_id,
+                lottery_info.bump,
                 prize_amount,
-            )
-            .unwrap();
-
-            invoke_signed(
-                &transfer_fee_ix,
-                &[lottery_ata.clone(), fee_ata.clone(), lottery_pda.clone()],
-                &[&[&lottery_id.key.to_bytes().clone()]],
             )?;
+            lottery_info.paid_prizes |= 1 << prize_index;
+            msg!(&*format!(
+                "prize tier {:?} paid: {:?}, fee: {:?}",
+                prize_index, prize_amount, fee_amount
+            ));
+            LotteryEvent::PrizePaid {
+                lottery_id: *lottery_id.key,
+                prize_index: prize_index as u8,
+                winner: ticket_info.buyer,
+                amount: prize_amount,
+                fee: fee_amount,
+            }
+            .emit();
 
-            invoke_signed(
-                &transfer_prize_ix,
-                &[lottery_ata.clone(), winner_ata.clone(), lottery_pda.clone()],
-                &[&[&lottery_id.key.to_bytes().clone()]],
-            )?;
+            let all_paid = (0..lottery_info.prize_count as usize)
+                .all(|i| lottery_info.paid_prizes & (1 << i) != 0);
+            if !all_paid {
+                Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+                return Ok(());
+            }
         }
-        let close_ix = TokenIns::close_account(
-            token_program.key,
-            lottery_ata.key,
-            lottery_authority.key,
-            lottery_pda.key,
-            &[],
-        )?;
-
-        invoke_signed(
-            &close_ix,
-            &[
-                lottery_ata.clone(),
-                lottery_authority.clone(),
-                lottery_pda.clone(),
-            ],
-            &[&[&lottery_id.key.to_bytes().clone()]],
+        if lottery_info.series != Pubkey::default() {
+            // an unsold round hands its pot to the next round through Rollover, which records it
+            if lottery_info.current_amount == 0
+                && Self::vault_balance(&lottery_info, lottery_ata)? != 0
+            {
+                return Err(LotteryError::RolloverRequired.into());
+            }
+            // the vault stays open for the next round of the series
+            lottery_info.set_status(LotteryStatus::Settled)?;
+            Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+            return Ok(());
+        }
+        if lottery_info.current_amount == 0 && lottery_info.rollover_in != 0 {
+            // closing the vault would hand the jackpot to the authority
+            return Err(LotteryError::RolloverRequired.into());
+        }
+        if !lottery_authority.is_writable {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        Self::close_vault(
+            token_program,
+            lottery_ata,
+            lottery_authority,
+            lottery_pda,
+            &vault_id,
+            lottery_info.bump,
         )?;
 
-        lottery_info.account_type = 4;
+        lottery_info.set_status(LotteryStatus::Settled)?;
         Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
 
         Ok(())
@@ -508,19 +957,19 @@ impl Processor {
         let lottery_id = next_account_info(account_info_iter)?;
         let ticket_id = next_account_info(account_info_iter)?;
         let buyer = next_account_info(account_info_iter)?;
-        let lottery_info = Lottery::unpack_unchecked(&lottery_id.data.borrow())?;
-        let mut ticket_info = Ticket::unpack_unchecked(&ticket_id.data.borrow())?;
-        if ticket_info.lottery_id != lottery_id.key.clone()  {
-            msg!("Wrong lottery id");
-            return Err(ProgramError::InvalidAccountData);
+        let lottery_info = Lottery::unpack_versioned(&lottery_id.data.borrow())?;
+        let mut ticket_info = Ticket::unpack_versioned(&ticket_id.data.borrow())?;
+        if ticket_info.lottery_id != *lottery_id.key  {
+            return Err(LotteryError::WrongTicket.into());
+        }
+        if lottery_info.status != LotteryStatus::Settled {
+            return Err(LotteryError::NotSettled.into());
         }
-        if lottery_info.account_type != 4 {
-            msg!("Wrong lottery type");
-            return Err(ProgramError::InvalidAccountData);
+        if ticket_info.buyer != *buyer.key{
+            return Err(LotteryError::WrongTicket.into());
         }
-        if ticket_info.buyer != buyer.key.clone(){
-            msg!("Wrong ticket");
-            return Err(ProgramError::InvalidAccountData);
+        if ticket_info.status != TicketStatus::Active {
+            return Err(LotteryError::TicketClosed.into());
         }
         
 
@@ -529,25 +978,1288 @@ impl Processor {
             buyer,
             
         ];
-        if Self::check_writable(writable_accounts) && !lottery_id.is_writable {
-            return Err(ProgramError::InvalidAccountData);
+        if Self::check_writable(writable_accounts) {
+            return Err(LotteryError::AccountNotWritable.into());
         }
         check_program_account(lottery_id.owner)?;
         check_program_account(ticket_id.owner)?;
         let ticket_balance = ticket_id.lamports();
         
-        **buyer.lamports.borrow_mut() = ticket_balance.checked_add(buyer.lamports()).unwrap();
+        **buyer.lamports.borrow_mut() = ticket_balance
+            .checked_add(buyer.lamports())
+            .ok_or(LotteryError::Overflow)?;
         **ticket_id.lamports.borrow_mut() = 0; 
-        ticket_info.account_type = 69;
+        LotteryEvent::TicketClosed {
+            lottery_id: *lottery_id.key,
+            ticket_id: *ticket_id.key,
+            buyer: ticket_info.buyer,
+            refunded: 0,
+        }
+        .emit();
+        if ticket_info.version == 0 {
+            // the drained account is gone after this transaction, no need to migrate it first
+            return Ok(());
+        }
+        ticket_info.status = TicketStatus::Closed;
+        Ticket::pack(ticket_info, &mut ticket_id.data.borrow_mut())?;
+        Ok(())
+    }
+    fn process_refund(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lottery_id = next_account_info(account_info_iter)?;
+        let ticket_id = next_account_info(account_info_iter)?;
+        let buyer = next_account_info(account_info_iter)?;
+        let buyer_token_account = next_account_info(account_info_iter)?;
+        let lottery_ata = next_account_info(account_info_iter)?;
+        let lottery_pda = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let lottery_authority = next_account_info(account_info_iter)?;
+
+        let writable_accounts = vec![lottery_id, ticket_id, buyer, buyer_token_account, lottery_ata];
+        if Self::check_writable(writable_accounts) {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        check_program_account(lottery_id.owner)?;
+        check_program_account(ticket_id.owner)?;
+        let mut lottery_info = Self::unpack_lottery(lottery_id)?;
+        let mut ticket_info = Self::unpack_ticket(ticket_id)?;
+
+        if lottery_info.status != LotteryStatus::Cancelled {
+            return Err(LotteryError::NotCancelled.into());
+        }
+        if ticket_info.status != TicketStatus::Active {
+            return Err(LotteryError::TicketClosed.into());
+        }
+        if ticket_info.lottery_id != *lottery_id.key {
+            return Err(LotteryError::WrongTicket.into());
+        }
+        if ticket_info.buyer != *buyer.key || !buyer.is_signer {
+            return Err(LotteryError::WrongTicket.into());
+        }
+        if lottery_info.token_reciever != *lottery_ata.key {
+            return Err(LotteryError::WrongTokenAccount.into());
+        }
+        if lottery_info.authority != *lottery_authority.key {
+            return Err(LotteryError::WrongAuthority.into());
+        }
+        Self::check_lottery_pda(&lottery_info, lottery_id, lottery_pda, program_id)?;
+        Self::check_token_program(&lottery_info, token_program)?;
+        let buyer_owner = if lottery_info.is_native() {
+            *buyer_token_account.key
+        } else {
+            TokenAccount::unpack(&buyer_token_account.data.borrow())?.owner
+        };
+        if buyer_owner != ticket_info.buyer {
+            return Err(LotteryError::WrongTokenAccount.into());
+        }
+        let vault_id = lottery_info.vault_id(lottery_id.key);
+
+        let refund_numbers = ticket_info
+            .end_number
+            .checked_sub(ticket_info.start_number)
+            .and_then(|numbers| numbers.checked_add(1))
+            .ok_or(LotteryError::Overflow)?;
+        let refund_amount = refund_numbers
+            .checked_mul(lottery_info.ticket_price)
+            .ok_or(LotteryError::Overflow)?;
+        Self::transfer_from_vault(
+            token_program,
+            lottery_ata,
+            buyer_token_account,
+            lottery_pda,
+            &vault_id,
+            lottery_info.bump,
+            refund_amount,
+        )?;
+        lottery_info.refunded_amount = lottery_info
+            .refunded_amount
+            .checked_add(refund_numbers)
+            .ok_or(LotteryError::Overflow)?;
+        msg!(&*format!(
+            "refunded {:?}, {:?} of {:?} returned",
+            refund_amount, lottery_info.refunded_amount, lottery_info.current_amount
+        ));
+
+        // the ticket is spent, hand its rent back like Close does
+        let ticket_balance = ticket_id.lamports();
+        **buyer.lamports.borrow_mut() = ticket_balance
+            .checked_add(buyer.lamports())
+            .ok_or(LotteryError::Overflow)?;
+        **ticket_id.lamports.borrow_mut() = 0;
+        ticket_info.status = TicketStatus::Closed;
+        LotteryEvent::TicketClosed {
+            lottery_id: *lottery_id.key,
+            ticket_id: *ticket_id.key,
+            buyer: ticket_info.buyer,
+            refunded: refund_amount,
+        }
+        .emit();
         Ticket::pack(ticket_info, &mut ticket_id.data.borrow_mut())?;
+
+        // a series round settles only once the shared vault is empty, the rest goes on with Rollover
+        let pot_left = lottery_info.rollover_in != 0
+            || (lottery_info.series != Pubkey::default()
+                && Self::vault_balance(&lottery_info, lottery_ata)? != 0);
+        if lottery_info.refunded_amount >= lottery_info.current_amount && !pot_left {
+            // the vault of a series round stays open for the next round
+            if lottery_info.series == Pubkey::default() {
+                if !lottery_authority.is_writable {
+                    return Err(LotteryError::AccountNotWritable.into());
+                }
+                Self::close_vault(
+                    token_program,
+                    lottery_ata,
+                    lottery_authority,
+                    lottery_pda,
+                    &vault_id,
+                    lottery_info.bump,
+                )?;
+            }
+            lottery_info.set_status(LotteryStatus::Settled)?;
+        }
+        // a rolled over pot stays in the vault until Rollover carries it on
+        Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
         Ok(())
     }
+    fn process_init_config(
+        accounts: &[AccountInfo],
+        fee_bps: u16,
+        max_fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let fee_receiver = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent = next_account_info(account_info_iter)?;
+
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (config_key, bump) = find_config_address(program_id);
+        if config_key != *config_account.key {
+            return Err(LotteryError::WrongConfig.into());
+        }
+        if !config_account.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if fee_bps > max_fee_bps || max_fee_bps > FEE_BPS_TOTAL {
+            return Err(LotteryError::FeeTooHigh.into());
+        }
+
+        let rent_info = Rent::from_account_info(rent)?;
+        let create_inx = system_instruction::create_account(
+            admin.key,
+            config_account.key,
+            rent_info.minimum_balance(Config::LEN),
+            Config::LEN.try_into().unwrap(),
+            program_id,
+        );
+        invoke_signed(
+            &create_inx,
+            &[
+                admin.clone(),
+                config_account.clone(),
+                system_program_account.clone(),
+            ],
+            &[&[CONFIG_SEED, &[bump]]],
+        )?;
+
+        let config_info = Config {
+            account_type: AccountType::Config,
+            admin: *admin.key,
+            fee_bps,
+            fee_receiver: *fee_receiver.key,
+            max_fee_bps,
+            bump,
+        };
+        Config::pack(config_info, &mut config_account.data.borrow_mut())?;
+        msg!(&*format!(
+            "Config initialized, fee: {:?} bps, max fee: {:?} bps, fee reciever: {:?}",
+            fee_bps, max_fee_bps, fee_receiver.key
+        ));
+        Ok(())
+    }
+
+    fn process_set_config(
+        accounts: &[AccountInfo],
+        fee_bps: u16,
+        max_fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let new_admin = next_account_info(account_info_iter)?;
+        let new_fee_receiver = next_account_info(account_info_iter)?;
+
+        let mut config_info = Self::unpack_config(config_account, program_id)?;
+        if !admin.is_signer || config_info.admin != *admin.key {
+            return Err(LotteryError::WrongAuthority.into());
+        }
+        if !config_account.is_writable {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        if max_fee_bps > config_info.max_fee_bps || fee_bps > max_fee_bps {
+            return Err(LotteryError::FeeTooHigh.into());
+        }
+
+        config_info.admin = *new_admin.key;
+        config_info.fee_receiver = *new_fee_receiver.key;
+        config_info.fee_bps = fee_bps;
+        config_info.max_fee_bps = max_fee_bps;
+        Config::pack(config_info, &mut config_account.data.borrow_mut())?;
+        msg!(&*format!(
+            "Config updated, fee: {:?} bps, max fee: {:?} bps, fee reciever: {:?}",
+            fee_bps, max_fee_bps, new_fee_receiver.key
+        ));
+        Ok(())
+    }
+    fn process_migrate(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let legacy_account = next_account_info(account_info_iter)?;
+        let payer = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent = next_account_info(account_info_iter)?;
+
+        check_program_account(legacy_account.owner)?;
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Self::check_writable(vec![legacy_account, payer]) {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        let rent_info = Rent::from_account_info(rent)?;
+
+        match legacy_account.data_len() {
+            LEGACY_LOTTERY_LEN => {
+                let mut lottery_info = Lottery::unpack_versioned(&legacy_account.data.borrow())?;
+                let (pda, bump) = find_lottery_address(legacy_account.key, program_id);
+                lottery_info.bump = bump;
+                lottery_info.version = LOTTERY_VERSION;
+                if lottery_info.status != LotteryStatus::Settled {
+                    lottery_info.token_reciever = Self::migrate_vault(
+                        &lottery_info,
+                        legacy_account,
+                        &pda,
+                        payer,
+                        system_program_account,
+                        rent,
+                        account_info_iter,
+                    )?;
+                }
+                Self::grow_account(
+                    legacy_account,
+                    payer,
+                    system_program_account,
+                    &rent_info,
+                    Lottery::LEN,
+                )?;
+                Lottery::pack(lottery_info, &mut legacy_account.data.borrow_mut())?;
+                msg!(&*format!("Lottery migrated to version {:?}", LOTTERY_VERSION));
+            }
+            LEGACY_TICKET_LEN => {
+                let mut ticket_info = Ticket::unpack_versioned(&legacy_account.data.borrow())?;
+                ticket_info.version = TICKET_VERSION;
+                Self::grow_account(
+                    legacy_account,
+                    payer,
+                    system_program_account,
+                    &rent_info,
+                    Ticket::LEN,
+                )?;
+                Ticket::pack(ticket_info, &mut legacy_account.data.borrow_mut())?;
+                msg!(&*format!("Ticket migrated to version {:?}", TICKET_VERSION));
+            }
+            _ => return Err(ProgramError::AccountAlreadyInitialized),
+        }
+        Ok(())
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn process_init_series(
+        accounts: &[AccountInfo],
+        max_amount: u64,
+        slot: u64,
+        prize_shares: Vec<u16>,
+        min_amount: u64,
+        ticket_price: u64,
+        max_per_wallet: u64,
+        crank_reward: u64,
+        duration: i64,
+        rollover_window: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let series_id = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let fee_authority = next_account_info(account_info_iter)?;
+        let vault_pda = next_account_info(account_info_iter)?;
+        let vault_ata = next_account_info(account_info_iter)?;
+        let fee_ata = next_account_info(account_info_iter)?;
+        let _ata_program = next_account_info(account_info_iter)?;
+        let token_mint = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+
+        let writable_accounts = vec![series_id, authority, vault_ata, fee_ata];
+        if Self::check_writable(writable_accounts) {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        if !authority.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let prize_shares =
+            Self::check_lottery_params(
+                prize_shares,
+                max_amount,
+                min_amount,
+                ticket_price,
+                crank_reward,
+                false,
+            )?;
+        if duration < 0 || rollover_window < 0 {
+            return Err(LotteryError::InvalidAmount.into());
+        }
+        let (pda, bump) = find_lottery_address(series_id.key, program_id);
+        if *vault_pda.key != pda {
+            return Err(LotteryError::WrongPda.into());
+        }
+        if get_associated_token_address(&pda, token_mint.key) != *vault_ata.key {
+            return Err(LotteryError::WrongTokenAccount.into());
+        }
+
+        let rent_info = Rent::from_account_info(rent)?;
+        let create_inx = system_instruction::create_account(
+            authority.key,
+            series_id.key,
+            rent_info.minimum_balance(Series::LEN),
+            Series::LEN.try_into().unwrap(),
+            program_id,
+        );
+        invoke(&create_inx, &[series_id.clone(), authority.clone()])?;
+        Self::init_token_accounts(
+            authority,
+            fee_authority,
+            vault_pda,
+            vault_ata,
+            fee_ata,
+            token_mint,
+            token_program,
+            system_program_account,
+            rent,
+            config_account,
+            program_id,
+        )?;
+
+        let mut series_prize_shares = [0; MAX_PRIZES];
+        series_prize_shares[..prize_shares.len()].copy_from_slice(&prize_shares);
+        let series_info = Series {
+            account_type: AccountType::Series,
+            authority: *authority.key,
+            token_mint: *token_mint.key,
+            token_reciever: *vault_ata.key,
+            fee_reciever: *fee_ata.key,
+            max_amount,
+            slot,
+            duration,
+            prize_count: prize_shares.len() as u8,
+            prize_shares: series_prize_shares,
+            min_amount,
+            ticket_price,
+            max_per_wallet,
+            crank_reward,
+            round: 0,
+            current_round: Pubkey::default(),
+            bump,
+            rollover_window,
+        };
+        Series::pack(series_info, &mut series_id.data.borrow_mut())?;
+        msg!(&*format!("Series initialized, id: {:?}", series_id.key));
+        Ok(())
+    }
+
+    fn process_next_round(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let series_id = next_account_info(account_info_iter)?;
+        let round_id = next_account_info(account_info_iter)?;
+        let previous_round = next_account_info(account_info_iter)?;
+        let payer = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let clock_account = next_account_info(account_info_iter)?;
+        let rent = next_account_info(account_info_iter)?;
+
+        check_program_account(series_id.owner)?;
+        if Self::check_writable(vec![series_id, round_id, payer]) {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut series_info = Series::unpack(&series_id.data.borrow())?;
+        let (round_key, round_bump) = find_round_address(series_id.key, series_info.round, program_id);
+        if round_key != *round_id.key {
+            return Err(LotteryError::WrongRound.into());
+        }
+        // the pot the previous round left in the vault if it rolled over
+        let rollover_in = if series_info.round != 0 {
+            if series_info.current_round != *previous_round.key {
+                return Err(LotteryError::WrongRound.into());
+            }
+            check_program_account(previous_round.owner)?;
+            let previous_info = Lottery::unpack(&previous_round.data.borrow())?;
+            if previous_info.status != LotteryStatus::Settled {
+                return Err(LotteryError::RoundNotSettled.into());
+            }
+            if previous_info.rollover_to == round_key {
+                previous_info.rollover_out
+            } else {
+                0
+            }
+        } else {
+            0
+        };
+
+        let rent_info = Rent::from_account_info(rent)?;
+        let create_inx = system_instruction::create_account(
+            payer.key,
+            round_id.key,
+            rent_info.minimum_balance(Lottery::LEN),
+            Lottery::LEN.try_into().unwrap(),
+            program_id,
+        );
+        invoke_signed(
+            &create_inx,
+            &[payer.clone(), round_id.clone(), system_program_account.clone()],
+            &[&[
+                ROUND_SEED,
+                &series_id.key.to_bytes(),
+                &series_info.round.to_le_bytes(),
+                &[round_bump],
+            ]],
+        )?;
+
+        let clock_info = clock::Clock::from_account_info(clock_account)?;
+        let mut lottery_info = Lottery::unpack_unchecked(&round_id.data.borrow())?;
+        if series_info.duration != 0 {
+            lottery_info.ended_slot = u64::MAX;
+            lottery_info.ended_timestamp = clock_info
+                .unix_timestamp
+                .checked_add(series_info.duration)
+                .ok_or(LotteryError::Overflow)?;
+        } else {
+            lottery_info.ended_slot = clock_info
+                .slot
+                .checked_add(series_info.slot)
+                .ok_or(LotteryError::Overflow)?;
+            lottery_info.ended_timestamp = 0;
+        }
+        lottery_info.account_type = AccountType::Lottery;
+        lottery_info.set_status(LotteryStatus::Open)?;
+        lottery_info.authority = series_info.authority;
+        lottery_info.lottery_number = 0;
+        lottery_info.max_amount = series_info.max_amount;
+        lottery_info.token_reciever = series_info.token_reciever;
+        lottery_info.fee_reciever = series_info.fee_reciever;
+        lottery_info.current_amount = 0;
+        lottery_info.token_mint = series_info.token_mint;
+        lottery_info.commitment = [0; 32];
+        lottery_info.closed_slot = 0;
+        lottery_info.prize_count = series_info.prize_count;
+        lottery_info.prize_shares = series_info.prize_shares;
+        lottery_info.winning_numbers = [0; MAX_PRIZES];
+        lottery_info.paid_prizes = 0;
+        lottery_info.prize_pool = 0;
+        lottery_info.min_amount = series_info.min_amount;
+        lottery_info.refunded_amount = 0;
+        lottery_info.ticket_price = series_info.ticket_price;
+        lottery_info.max_per_wallet = series_info.max_per_wallet;
+        lottery_info.crank_reward = series_info.crank_reward;
+        lottery_info.bump = series_info.bump;
+        lottery_info.version = LOTTERY_VERSION;
+        lottery_info.start_slot = 0;
+        lottery_info.start_timestamp = 0;
+        lottery_info.series = *series_id.key;
+        lottery_info.rollover_window = series_info.rollover_window;
+        lottery_info.rollover_in = rollover_in;
+        LotteryEvent::LotteryCreated {
+            lottery_id: *round_id.key,
+            authority: lottery_info.authority,
+            token_mint: lottery_info.token_mint,
+            max_amount: lottery_info.max_amount,
+            ended_slot: lottery_info.ended_slot,
+            ticket_price: lottery_info.ticket_price,
+            ended_timestamp: lottery_info.ended_timestamp,
+            start_slot: 0,
+            start_timestamp: 0,
+        }
+        .emit();
+        Lottery::pack(lottery_info, &mut round_id.data.borrow_mut())?;
+
+        msg!(&*format!(
+            "Round {:?} of series {:?} opened, id: {:?}",
+            series_info.round, series_id.key, round_id.key
+        ));
+        series_info.current_round = *round_id.key;
+        series_info.round = series_info
+            .round
+            .checked_add(1)
+            .ok_or(LotteryError::Overflow)?;
+        Series::pack(series_info, &mut series_id.data.borrow_mut())?;
+        Ok(())
+    }
+    fn process_rollover(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lottery_id = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let lottery_ata = next_account_info(account_info_iter)?;
+        let lottery_pda = next_account_info(account_info_iter)?;
+        let successor_id = next_account_info(account_info_iter)?;
+        let successor_ata = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let clock_account = next_account_info(account_info_iter)?;
+
+        let writable_accounts = vec![lottery_id, authority, lottery_ata, successor_id, successor_ata];
+        if Self::check_writable(writable_accounts) {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        check_program_account(lottery_id.owner)?;
+        check_program_account(successor_id.owner)?;
+        let mut lottery_info = Self::unpack_lottery(lottery_id)?;
+        let clock = clock::Clock::from_account_info(clock_account)?;
+
+        if !authority.is_signer || lottery_info.authority != *authority.key {
+            return Err(LotteryError::WrongAuthority.into());
+        }
+        // the pot of a round without winners stays in the series vault, so it needs no rollover window
+        let no_winner_round = lottery_info.series != Pubkey::default()
+            && (lottery_info.current_amount == 0 || lottery_info.status == LotteryStatus::Cancelled);
+        if lottery_info.rollover_window == 0 && !no_winner_round {
+            return Err(LotteryError::RolloverDisabled.into());
+        }
+        match lottery_info.status {
+            LotteryStatus::Drawn => {
+                if lottery_info.claim_window_open(&clock) {
+                    return Err(LotteryError::PotStillClaimable.into());
+                }
+            }
+            LotteryStatus::Cancelled => {
+                if lottery_info.refunded_amount < lottery_info.current_amount {
+                    return Err(LotteryError::PotStillClaimable.into());
+                }
+            }
+            _ => return Err(LotteryError::NotDrawn.into()),
+        }
+        if lottery_info.token_reciever != *lottery_ata.key {
+            return Err(LotteryError::WrongTokenAccount.into());
+        }
+        Self::check_lottery_pda(&lottery_info, lottery_id, lottery_pda, program_id)?;
+        Self::check_token_program(&lottery_info, token_program)?;
+        let amount = Self::vault_balance(&lottery_info, lottery_ata)?;
+
+        let successor_key = if lottery_info.series != Pubkey::default() {
+            // the next round shares the vault, NextRound picks the amount up from this round
+            if lottery_info.series != *successor_id.key {
+                return Err(LotteryError::InvalidSuccessor.into());
+            }
+            let series_info = Series::unpack(&successor_id.data.borrow())?;
+            if series_info.current_round != *lottery_id.key {
+                return Err(LotteryError::WrongRound.into());
+            }
+            if *successor_ata.key != *lottery_ata.key {
+                return Err(LotteryError::WrongTokenAccount.into());
+            }
+            find_round_address(successor_id.key, series_info.round, program_id).0
+        } else {
+            let mut successor_info = Self::unpack_lottery(successor_id)?;
+            if *successor_id.key == *lottery_id.key
+                || successor_info.status != LotteryStatus::Open
+                || successor_info.token_mint != lottery_info.token_mint
+                || successor_info.authority != lottery_info.authority
+                || successor_info.rollover_window == 0
+            {
+                return Err(LotteryError::InvalidSuccessor.into());
+            }
+            if successor_info.token_reciever != *successor_ata.key {
+                return Err(LotteryError::WrongTokenAccount.into());
+            }
+            Self::transfer_from_vault(
+                token_program,
+                lottery_ata,
+                successor_ata,
+                lottery_pda,
+                lottery_id.key,
+                lottery_info.bump,
+                amount,
+            )?;
+            Self::close_vault(
+                token_program,
+                lottery_ata,
+                authority,
+                lottery_pda,
+                lottery_id.key,
+                lottery_info.bump,
+            )?;
+            successor_info.rollover_in = successor_info
+                .rollover_in
+                .checked_add(amount)
+                .ok_or(LotteryError::Overflow)?;
+            Lottery::pack(successor_info, &mut successor_id.data.borrow_mut())?;
+            *successor_id.key
+        };
+
+        lottery_info.rollover_to = successor_key;
+        lottery_info.rollover_out = amount;
+        lottery_info.set_status(LotteryStatus::Settled)?;
+        msg!(&*format!("rolled over {:?} into {:?}", amount, successor_key));
+        LotteryEvent::RolledOver {
+            lottery_id: *lottery_id.key,
+            successor: successor_key,
+            amount,
+        }
+        .emit();
+        Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+        Ok(())
+    }
+    fn process_set_authority(accounts: &[AccountInfo], propose: bool, _program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lottery_id = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let new_authority = next_account_info(account_info_iter)?;
+
+        if !lottery_id.is_writable {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        check_program_account(lottery_id.owner)?;
+        let mut lottery_info = Self::unpack_lottery(lottery_id)?;
+        if !authority.is_signer || lottery_info.authority != *authority.key {
+            return Err(LotteryError::WrongAuthority.into());
+        }
+
+        if propose {
+            // the default pubkey withdraws the proposal
+            lottery_info.pending_authority = *new_authority.key;
+            msg!(&*format!("proposed authority {:?}", new_authority.key));
+        } else {
+            if *new_authority.key == Pubkey::default() {
+                return Err(LotteryError::WrongAuthority.into());
+            }
+            Self::change_authority(&mut lottery_info, lottery_id.key, new_authority.key);
+        }
+        Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+        Ok(())
+    }
+    fn process_accept_authority(accounts: &[AccountInfo], _program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lottery_id = next_account_info(account_info_iter)?;
+        let pending_authority = next_account_info(account_info_iter)?;
+
+        if !lottery_id.is_writable {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        check_program_account(lottery_id.owner)?;
+        let mut lottery_info = Self::unpack_lottery(lottery_id)?;
+        if !pending_authority.is_signer
+            || lottery_info.pending_authority == Pubkey::default()
+            || lottery_info.pending_authority != *pending_authority.key
+        {
+            return Err(LotteryError::WrongAuthority.into());
+        }
+
+        Self::change_authority(&mut lottery_info, lottery_id.key, pending_authority.key);
+        Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+        Ok(())
+    }
+    fn process_update_lottery(
+        accounts: &[AccountInfo],
+        max_amount: u64,
+        ended_slot: u64,
+        ended_timestamp: i64,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lottery_id = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let clock_account = next_account_info(account_info_iter)?;
+
+        if !lottery_id.is_writable {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+        check_program_account(lottery_id.owner)?;
+        let mut lottery_info = Self::unpack_lottery(lottery_id)?;
+        let clock = clock::Clock::from_account_info(clock_account)?;
+        if !authority.is_signer || lottery_info.authority != *authority.key {
+            return Err(LotteryError::WrongAuthority.into());
+        }
+        if lottery_info.status != LotteryStatus::Open {
+            return Err(LotteryError::LotteryNotOpen.into());
+        }
+        // once the sales are over anyone can draw, moving the end then would race the crank
+        if lottery_info.has_ended(&clock) {
+            return Err(LotteryError::LotteryEnded.into());
+        }
+        // 0 keeps the current value
+        let max_amount = if max_amount == 0 { lottery_info.max_amount } else { max_amount };
+        let ended_slot = if ended_slot == 0 { lottery_info.ended_slot } else { ended_slot };
+        let ended_timestamp = if ended_timestamp == 0 {
+            lottery_info.ended_timestamp
+        } else {
+            ended_timestamp
+        };
+        // the cap can come down as long as no sold ticket is left above it
+        if max_amount < lottery_info.current_amount
+            || ended_slot < lottery_info.ended_slot
+            || ended_timestamp < lottery_info.ended_timestamp
+        {
+            return Err(LotteryError::LotteryShortened.into());
+        }
+        // adding an end timestamp would end a lottery running by slot whichever comes first
+        if lottery_info.ended_timestamp == 0 && ended_timestamp != 0 {
+            return Err(LotteryError::InvalidEndTimestamp.into());
+        }
+        if max_amount.checked_mul(lottery_info.ticket_price).is_none() {
+            return Err(LotteryError::InvalidAmount.into());
+        }
+
+        lottery_info.max_amount = max_amount;
+        lottery_info.ended_slot = ended_slot;
+        lottery_info.ended_timestamp = ended_timestamp;
+        if lottery_info.current_amount == lottery_info.max_amount {
+            lottery_info.set_status(LotteryStatus::SoldOut)?;
+            msg!("lottery sold out");
+        }
+        msg!(&*format!(
+            "lottery updated, max amount: {:?}, ended slot: {:?}, end timestamp: {:?}",
+            max_amount, ended_slot, ended_timestamp
+        ));
+        LotteryEvent::LotteryUpdated {
+            lottery_id: *lottery_id.key,
+            max_amount,
+            ended_slot,
+            ended_timestamp,
+        }
+        .emit();
+        Lottery::pack(lottery_info, &mut lottery_id.data.borrow_mut())?;
+        Ok(())
+    }
+    /// Hands the lottery over and drops any pending proposal
+    fn change_authority(lottery_info: &mut Lottery, lottery_id: &Pubkey, new_authority: &Pubkey) {
+        let old_authority = lottery_info.authority;
+        lottery_info.authority = *new_authority;
+        lottery_info.pending_authority = Pubkey::default();
+        msg!(&*format!("authority changed from {:?} to {:?}", old_authority, new_authority));
+        LotteryEvent::AuthorityChanged {
+            lottery_id: *lottery_id,
+            old_authority,
+            new_authority: *new_authority,
+        }
+        .emit();
+    }
+    /// Moves the pot of a legacy lottery into the vault of its new PDA, returns the new vault
+    fn migrate_vault<'a, 'b: 'a>(
+        lottery_info: &Lottery,
+        lottery_id: &AccountInfo<'b>,
+        pda: &Pubkey,
+        payer: &AccountInfo<'b>,
+        system_program_account: &AccountInfo<'b>,
+        rent: &AccountInfo<'b>,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    ) -> Result<Pubkey, ProgramError> {
+        let lottery_authority = next_account_info(account_info_iter)?;
+        let legacy_ata = next_account_info(account_info_iter)?;
+        let legacy_pda = next_account_info(account_info_iter)?;
+        let lottery_ata = next_account_info(account_info_iter)?;
+        let lottery_pda = next_account_info(account_info_iter)?;
+        let token_mint = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let _ata_program = next_account_info(account_info_iter)?;
+
+        if lottery_info.authority != *lottery_authority.key {
+            return Err(LotteryError::WrongAuthority.into());
+        }
+        if lottery_info.token_reciever != *legacy_ata.key
+            || get_associated_token_address(pda, token_mint.key) != *lottery_ata.key
+        {
+            return Err(LotteryError::WrongTokenAccount.into());
+        }
+        let lottery_id_bytes = lottery_id.key.to_bytes();
+        let legacy_seeds: &[&[u8]] = &[&lottery_id_bytes];
+        if Pubkey::create_program_address(legacy_seeds, lottery_id.owner)? != *legacy_pda.key
+            || pda != lottery_pda.key
+        {
+            return Err(LotteryError::WrongPda.into());
+        }
+        if Self::check_writable(vec![lottery_authority, legacy_ata, lottery_ata]) {
+            return Err(LotteryError::AccountNotWritable.into());
+        }
+
+        if lottery_ata.owner != token_program.key {
+            let lottery_ata_ix =
+                create_associated_token_account(payer.key, lottery_pda.key, token_mint.key);
+            invoke(
+                &lottery_ata_ix,
+                &[
+                    payer.clone(),
+                    lottery_pda.clone(),
+                    lottery_ata.clone(),
+                    system_program_account.clone(),
+                    rent.clone(),
+                    token_program.clone(),
+                    token_mint.clone(),
+                ],
+            )?;
+        }
+        let legacy_ata_info = TokenAccount::unpack(&legacy_ata.data.borrow())?;
+        let transfer_ix = TokenIns::transfer(
+            token_program.key,
+            legacy_ata.key,
+            lottery_ata.key,
+            legacy_pda.key,
+            &[],
+            legacy_ata_info.amount,
+        )?;
+        invoke_signed(
+            &transfer_ix,
+            &[legacy_ata.clone(), lottery_ata.clone(), legacy_pda.clone()],
+            &[legacy_seeds],
+        )?;
+        let close_ix = TokenIns::close_account(
+            token_program.key,
+            legacy_ata.key,
+            lottery_authority.key,
+            legacy_pda.key,
+            &[],
+        )?;
+        invoke_signed(
+            &close_ix,
+            &[
+                legacy_ata.clone(),
+                lottery_authority.clone(),
+                legacy_pda.clone(),
+            ],
+            &[legacy_seeds],
+        )?;
+        msg!(&*format!(
+            "Moved {:?} into the new vault {:?}",
+            legacy_ata_info.amount, lottery_ata.key
+        ));
+        Ok(*lottery_ata.key)
+    }
+    /// Reallocates a program account to `new_len`, the payer covers the extra rent
+    fn grow_account<'a>(
+        account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        rent_info: &Rent,
+        new_len: usize,
+    ) -> ProgramResult {
+        let missing_rent = rent_info
+            .minimum_balance(new_len)
+            .saturating_sub(account.lamports());
+        if missing_rent != 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, missing_rent),
+                &[payer.clone(), account.clone(), system_program_account.clone()],
+            )?;
+        }
+        account.realloc(new_len, true)
+    }
+    /// Pays the caller of Reveal up to `crank_reward` out of the pot
+    fn pay_crank_reward<'a, 'b: 'a>(
+        lottery_info: &Lottery,
+        lottery_id: &AccountInfo<'b>,
+        caller: &AccountInfo<'b>,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let lottery_ata = next_account_info(account_info_iter)?;
+        let caller_token_account = next_account_info(account_info_iter)?;
+        let lottery_pda = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+
+        if lottery_info.token_reciever != *lottery_ata.key {
+            return Err(LotteryError::WrongTokenAccount.into());
+        }
+        Self::check_lottery_pda(lottery_info, lottery_id, lottery_pda, program_id)?;
+        Self::check_token_program(lottery_info, token_program)?;
+        let vault_amount = Self::vault_balance(lottery_info, lottery_ata)?;
+        let reward = std::cmp::min(lottery_info.crank_reward, vault_amount);
+        if reward == 0 {
+            return Ok(());
+        }
+        Self::transfer_from_vault(
+            token_program,
+            lottery_ata,
+            caller_token_account,
+            lottery_pda,
+            &lottery_info.vault_id(lottery_id.key),
+            lottery_info.bump,
+            reward,
+        )?;
+        msg!(&*format!("crank reward {:?} paid to {:?}", reward, caller.key));
+        LotteryEvent::CrankRewardPaid {
+            lottery_id: *lottery_id.key,
+            caller: *caller.key,
+            amount: reward,
+        }
+        .emit();
+        Ok(())
+    }
+    /// Moves `amount` out of the lottery vault, signed by the PDA of `vault_id`.
+    /// A native SOL vault is the PDA itself and pays lamports through the system program
+    fn transfer_from_vault<'a>(
+        token_program: &AccountInfo<'a>,
+        lottery_ata: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        lottery_pda: &AccountInfo<'a>,
+        vault_id: &Pubkey,
+        bump: u8,
+        amount: u64,
+    ) -> ProgramResult {
+        if *token_program.key == system_program::id() {
+            // the runtime refuses to leave a new account below rent exemption, so the transfer
+            // would fail until the wallet is funded anyway
+            if amount != 0
+                && destination.lamports() == 0
+                && amount < Rent::get()?.minimum_balance(0)
+            {
+                msg!(&*format!("fund {:?} before it can receive {:?} lamports", destination.key, amount));
+                return Err(LotteryError::PayoutBelowRent.into());
+            }
+            return invoke_signed(
+                &system_instruction::transfer(lottery_pda.key, destination.key, amount),
+                &[lottery_pda.clone(), destination.clone(), token_program.clone()],
+                &[&[LOTTERY_SEED, &vault_id.to_bytes(), &[bump]]],
+            );
+        }
+        let transfer_ix = TokenIns::transfer(
+            token_program.key,
+            lottery_ata.key,
+            destination.key,
+            lottery_pda.key,
+            &[],
+            amount,
+        )?;
+        invoke_signed(
+            &transfer_ix,
+            &[lottery_ata.clone(), destination.clone(), lottery_pda.clone()],
+            &[&[LOTTERY_SEED, &vault_id.to_bytes(), &[bump]]],
+        )
+    }
+    /// Empties the vault into `receiver` and closes it, a native SOL vault hands over its rent reserve too
+    fn close_vault<'a>(
+        token_program: &AccountInfo<'a>,
+        lottery_ata: &AccountInfo<'a>,
+        receiver: &AccountInfo<'a>,
+        lottery_pda: &AccountInfo<'a>,
+        vault_id: &Pubkey,
+        bump: u8,
+    ) -> ProgramResult {
+        if *token_program.key == system_program::id() {
+            return Self::transfer_from_vault(
+                token_program,
+                lottery_ata,
+                receiver,
+                lottery_pda,
+                vault_id,
+                bump,
+                lottery_pda.lamports(),
+            );
+        }
+        let close_ix = TokenIns::close_account(
+            token_program.key,
+            lottery_ata.key,
+            receiver.key,
+            lottery_pda.key,
+            &[],
+        )?;
+        invoke_signed(
+            &close_ix,
+            &[lottery_ata.clone(), receiver.clone(), lottery_pda.clone()],
+            &[&[LOTTERY_SEED, &vault_id.to_bytes(), &[bump]]],
+        )
+    }
+    /// Pot held by the vault, a native SOL vault keeps its rent exempt reserve out of it
+    fn vault_balance(lottery_info: &Lottery, lottery_ata: &AccountInfo) -> Result<u64, ProgramError> {
+        if lottery_info.is_native() {
+            let reserve = Rent::get()?.minimum_balance(0);
+            return Ok(lottery_ata.lamports().saturating_sub(reserve));
+        }
+        Ok(TokenAccount::unpack(&lottery_ata.data.borrow())?.amount)
+    }
+    /// The lottery moves its funds with the SPL token program, or the system program for native SOL
+    fn check_token_program(lottery_info: &Lottery, token_program: &AccountInfo) -> ProgramResult {
+        if *token_program.key != token_program_id(&lottery_info.token_mint) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(())
+    }
+    fn emit_drawn(lottery_info: &Lottery, lottery_id: &Pubkey) {
+        LotteryEvent::Drawn {
+            lottery_id: *lottery_id,
+            winning_numbers: lottery_info.winning_numbers[..lottery_info.prize_count as usize]
+                .to_vec(),
+        }
+        .emit();
+    }
+    /// Picks one number per prize tier, distinct as long as enough numbers were sold
+    fn draw_winning_numbers(lottery_info: &mut Lottery, random_data: &[u8]) {
+        let prize_count = lottery_info.prize_count as usize;
+        let current_amount = lottery_info.current_amount;
+        let mut winning_numbers = [0u64; MAX_PRIZES];
+        for i in 0..prize_count {
+            let mut tier_data = random_data.to_vec();
+            if i != 0 {
+                tier_data.push(i as u8);
+            }
+            let mut number = Self::winning_number(&tier_data, current_amount);
+            if current_amount >= prize_count as u64 {
+                while winning_numbers[..i].contains(&number) {
+                    number = number % current_amount + 1;
+                }
+            }
+            winning_numbers[i] = number;
+        }
+        lottery_info.winning_numbers = winning_numbers;
+        lottery_info.lottery_number = winning_numbers[0];
+    }
+    fn winning_number(random_data: &[u8], current_amount: u64) -> u64 {
+        msg!("hashing number");
+        let random_number_hash: [u8; 8] = hash::hash(random_data).to_bytes()[0..8]
+            .try_into()
+            .unwrap();
+        let mut random_number = 0;
+        if current_amount != 0 {
+            random_number = u64::from_le_bytes(random_number_hash) % current_amount;
+            if random_number == 0 {
+                random_number = current_amount;
+            }
+        }
+        random_number
+    }
+    /// Returns the most recent (slot, hash) entry of the SlotHashes sysvar
+    fn latest_slot_hash(slot_hash_account: &AccountInfo) -> Result<(u64, [u8; 32]), ProgramError> {
+        if *slot_hash_account.key != slot_hashes::id() {
+            return Err(LotteryError::WrongSysvar.into());
+        }
+        let data = slot_hash_account.data.borrow();
+        // bincode layout: u64 entry count followed by (u64 slot, [u8; 32] hash) entries
+        if data.len() < 48 || u64::from_le_bytes(data[0..8].try_into().unwrap()) == 0 {
+            return Err(LotteryError::SlotHashNotAvailable.into());
+        }
+        let slot = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        let hash: [u8; 32] = data[16..48].try_into().unwrap();
+        Ok((slot, hash))
+    }
+    /// Hash of `slot`, or of the first slot after it that has one if it was skipped.
+    /// Fails until that slot is in SlotHashes and once it has dropped out of it
+    fn slot_hash_from(
+        slot_hash_account: &AccountInfo,
+        slot: u64,
+    ) -> Result<(u64, [u8; 32]), ProgramError> {
+        if *slot_hash_account.key != slot_hashes::id() {
+            return Err(LotteryError::WrongSysvar.into());
+        }
+        let data = slot_hash_account.data.borrow();
+        if data.len() < 8 {
+            return Err(LotteryError::SlotHashNotAvailable.into());
+        }
+        let count = u64::from_le_bytes(data[0..8].try_into().unwrap()) as usize;
+        // entries are sorted newest first, the match is the last one at or after `slot`
+        let mut found = None;
+        for entry in data[8..].chunks_exact(40).take(count) {
+            let entry_slot = u64::from_le_bytes(entry[0..8].try_into().unwrap());
+            if entry_slot < slot {
+                return found.ok_or_else(|| LotteryError::SlotHashNotAvailable.into());
+            }
+            found = Some((entry_slot, entry[8..40].try_into().unwrap()));
+        }
+        // without an older entry a skipped slot can't be told apart from one that dropped out
+        match found {
+            Some((entry_slot, hash)) if entry_slot == slot => Ok((entry_slot, hash)),
+            _ => Err(LotteryError::SlotHashNotAvailable.into()),
+        }
+    }
+    /// Prize shares with the single winner default filled in, and the amount checks of a lottery
+    fn check_lottery_params(
+        prize_shares: Vec<u16>,
+        max_amount: u64,
+        min_amount: u64,
+        ticket_price: u64,
+        crank_reward: u64,
+        committed: bool,
+    ) -> Result<Vec<u16>, ProgramError> {
+        let prize_shares = if prize_shares.is_empty() {
+            vec![PRIZE_SHARE_TOTAL]
+        } else {
+            prize_shares
+        };
+        if prize_shares.len() > MAX_PRIZES
+            || prize_shares.contains(&0)
+            || prize_shares.iter().map(|share| *share as u32).sum::<u32>()
+                != PRIZE_SHARE_TOTAL as u32
+        {
+            return Err(LotteryError::InvalidPrizeShares.into());
+        }
+
+        if min_amount > max_amount {
+            return Err(LotteryError::InvalidAmount.into());
+        }
+        if ticket_price == 0 || max_amount.checked_mul(ticket_price).is_none() {
+            return Err(LotteryError::InvalidAmount.into());
+        }
+        // a reward for an uncommitted draw would pay whoever retries Draw until the numbers suit
+        // them, and a large one would let the authority crank the pot away
+        if crank_reward != 0 && (!committed || crank_reward > ticket_price) {
+            return Err(LotteryError::InvalidCrankReward.into());
+        }
+        Ok(prize_shares)
+    }
+    /// Checks the fee reciever against the config and creates the fee ATA and the vault if missing,
+    /// a native SOL vault only gets its rent exempt reserve
+    #[allow(clippy::too_many_arguments)]
+    fn init_token_accounts<'a>(
+        authority: &AccountInfo<'a>,
+        fee_authority: &AccountInfo<'a>,
+        lottery_pda: &AccountInfo<'a>,
+        lottery_ata: &AccountInfo<'a>,
+        fee_ata: &AccountInfo<'a>,
+        token_mint: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        rent: &AccountInfo<'a>,
+        config_account: &AccountInfo<'a>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let config_info = Self::unpack_config(config_account, program_id)?;
+        check_fee_account(&config_info, fee_authority.key)?;
+
+        if is_native_mint(token_mint.key) {
+            // the PDA holds the lamports and the fee authority is paid directly
+            if *fee_ata.key != *fee_authority.key {
+                return Err(LotteryError::WrongFeeAccount.into());
+            }
+            if *lottery_ata.key != *lottery_pda.key {
+                return Err(LotteryError::WrongTokenAccount.into());
+            }
+            let rent_info = Rent::from_account_info(rent)?;
+            let missing_rent = rent_info
+                .minimum_balance(0)
+                .saturating_sub(lottery_pda.lamports());
+            if missing_rent != 0 {
+                invoke(
+                    &system_instruction::transfer(authority.key, lottery_pda.key, missing_rent),
+                    &[
+                        authority.clone(),
+                        lottery_pda.clone(),
+                        system_program_account.clone(),
+                    ],
+                )?;
+            }
+            return Ok(());
+        }
+
+        if get_associated_token_address(fee_authority.key, token_mint.key) != *fee_ata.key {
+            return Err(LotteryError::WrongFeeAccount.into());
+        }
+        //msg!("fee_ata is right");
+        if fee_ata.owner != token_program.key {
+            let fee_ata_ix = create_associated_token_account(
+                authority.key,
+                &fee_authority.key.clone(),
+                token_mint.key,
+            );
+            invoke(
+                &fee_ata_ix,
+                &[
+                    authority.clone(),
+                    fee_authority.clone(),
+                    fee_ata.clone(),
+                    system_program_account.clone(),
+                    rent.clone(),
+                    token_program.clone(),
+                    token_mint.clone(),
+                ],
+            )?;
+        }
+
+        if lottery_ata.owner != token_program.key {
+            let lottery_ata_ix =
+                create_associated_token_account(authority.key, lottery_pda.key, token_mint.key);
+            invoke(
+                &lottery_ata_ix,
+                &[
+                    authority.clone(),
+                    lottery_pda.clone(),
+                    lottery_ata.clone(),
+                    system_program_account.clone(),
+                    rent.clone(),
+                    token_program.clone(),
+                    token_mint.clone(),
+                ],
+            )?;
+        }
+
+        let fee_token_account = TokenAccount::unpack(&fee_ata.data.borrow())?;
+
+        if fee_token_account.owner != *fee_authority.key {
+            return Err(LotteryError::WrongFeeAccount.into());
+        }
+        Ok(())
+    }
+    /// Checks `lottery_pda` is the vault authority of the lottery or of its series, derived with
+    /// the stored bump
+    fn check_lottery_pda(
+        lottery_info: &Lottery,
+        lottery_id: &AccountInfo,
+        lottery_pda: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Pubkey, ProgramError> {
+        let pda = Pubkey::create_program_address(
+            &[
+                LOTTERY_SEED,
+                &lottery_info.vault_id(lottery_id.key).to_bytes(),
+                &[lottery_info.bump],
+            ],
+            program_id,
+        )?;
+        if pda != *lottery_pda.key {
+            return Err(LotteryError::WrongPda.into());
+        }
+        Ok(pda)
+    }
+    /// Lottery state of an account the handler writes back, legacy accounts need Migrate first
+    fn unpack_lottery(lottery_id: &AccountInfo) -> Result<Lottery, ProgramError> {
+        if lottery_id.data_len() == LEGACY_LOTTERY_LEN {
+            return Err(LotteryError::AccountNeedsMigration.into());
+        }
+        Lottery::unpack(&lottery_id.data.borrow())
+    }
+    fn unpack_ticket(ticket_id: &AccountInfo) -> Result<Ticket, ProgramError> {
+        if ticket_id.data_len() == LEGACY_TICKET_LEN {
+            return Err(LotteryError::AccountNeedsMigration.into());
+        }
+        Ticket::unpack(&ticket_id.data.borrow())
+    }
+    fn unpack_config(config_account: &AccountInfo, program_id: &Pubkey) -> Result<Config, ProgramError> {
+        check_program_account(config_account.owner)?;
+        let config_info = Config::unpack(&config_account.data.borrow())?;
+        let config_key =
+            Pubkey::create_program_address(&[CONFIG_SEED, &[config_info.bump]], program_id)?;
+        if config_key != *config_account.key {
+            return Err(LotteryError::WrongConfig.into());
+        }
+        Ok(config_info)
+    }
+    /// True when any of the accounts is read-only
     fn check_writable(accounts: Vec<&AccountInfo>) -> bool {
         for x in accounts.iter() {
-            if x.is_writable {
-                return false;
+            if !x.is_writable {
+                return true;
             }
         }
-        return true;
+        false
     }
 }
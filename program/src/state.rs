@@ -1,12 +1,73 @@
 use solana_program::{
+    clock::Clock,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
 
+use crate::{error::LotteryError, is_native_mint};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use std::convert::TryInto;
+
+/// Most prize tiers a single lottery can pay out
+pub const MAX_PRIZES: usize = 5;
+/// Prize shares are expressed in basis points of the pot
+pub const PRIZE_SHARE_TOTAL: u16 = 10_000;
+/// Protocol fee is expressed in basis points of each prize
+pub const FEE_BPS_TOTAL: u16 = 10_000;
+/// Seed of the singleton program config PDA
+pub const CONFIG_SEED: &[u8] = b"config";
+/// Seed prefix of the per (lottery, buyer) purchase record PDA
+pub const BUYER_RECORD_SEED: &[u8] = b"buyer";
+/// Seed prefix of the lottery PDA owning the vault
+pub const LOTTERY_SEED: &[u8] = b"lottery";
+/// Seed prefix of the ticket PDA for a (lottery, buyer, purchase index)
+pub const TICKET_SEED: &[u8] = b"ticket";
+/// Seed prefix of the lottery account of a (series, round index)
+pub const ROUND_SEED: &[u8] = b"round";
+/// Layout version written by this program, older accounts go through Migrate
+pub const LOTTERY_VERSION: u8 = 1;
+pub const TICKET_VERSION: u8 = 1;
+/// Size of the accounts created before layouts were versioned
+pub const LEGACY_LOTTERY_LEN: usize = 161;
+pub const LEGACY_TICKET_LEN: usize = 81;
+/// Offset of the lottery status byte, for memcmp filters
+pub const LOTTERY_STATUS_OFFSET: usize = 303;
+/// A committed lottery is drawn with the hash of the first slot this many slots after its sales closed
+pub const REVEAL_SLOT_OFFSET: u64 = 10;
+/// Slots after the reveal slot the seed can still be revealed in, well inside the 512 SlotHashes entries
+pub const REVEAL_WINDOW: u64 = 256;
+
+/// Kind of a program account, the first byte of every layout
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountType {
+    Uninitialized = 0,
+    Lottery = 1,
+    Ticket = 2,
+    Config = 7,
+    BuyerRecord = 8,
+    Series = 9,
+}
+/// Where a lottery is in its life, the values match the account types
+/// that carried the status in legacy accounts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LotteryStatus {
+    Uninitialized = 0,
+    Open = 1,
+    Drawn = 3,
+    Settled = 4,
+    AwaitingReveal = 5,
+    Cancelled = 6,
+    SoldOut = 7,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TicketStatus {
+    Active = 0,
+    Closed = 1,
+}
+
 pub struct Lottery {
-    pub account_type: u8,       //1 is lottery ,3 is ended Lottery size:1
+    pub account_type: AccountType, //size:1
     pub authority: Pubkey,      //size:32
     pub token_reciever: Pubkey, //size:32
     pub fee_reciever: Pubkey,   //size:32
@@ -14,40 +75,427 @@ pub struct Lottery {
     pub ended_slot: u64,        //size:8
     pub lottery_number: u64,    //size:8
     pub current_amount: u64,    //size:8
-    pub token_mint: Pubkey
-                                //Lottery account size should be 161 Bytes
+    pub token_mint: Pubkey,     //system program id for a native SOL lottery size:32
+    pub commitment: [u8; 32],   //hash of the authority's draw seed, all zero if not committed size:32
+    pub closed_slot: u64,       //slot the sales were closed at for a committed draw size:8
+    pub prize_count: u8,        //number of prize tiers size:1
+    pub prize_shares: [u16; MAX_PRIZES], //share of the pot per tier in basis points size:10
+    pub winning_numbers: [u64; MAX_PRIZES], //winning number per tier, lottery_number is tier 0 size:40
+    pub paid_prizes: u8,        //bit i is set once tier i has been paid size:1
+    pub prize_pool: u64,        //pot balance when the first tier was paid size:8
+    pub min_amount: u64,        //lottery is cancelled if it ends below this, 0 to always draw size:8
+    pub refunded_amount: u64,   //numbers refunded so far after a cancel size:8
+    pub ticket_price: u64,      //token amount paid per ticket number size:8
+    pub max_per_wallet: u64,    //most tickets a single buyer can hold, 0 for no cap size:8
+    pub crank_reward: u64,      //paid from the pot to whoever calls Reveal size:8
+    pub bump: u8,               //bump of the lottery PDA owning the vault size:1
+    pub version: u8,            //layout version, 0 for legacy accounts size:1
+    pub status: LotteryStatus,  //size:1
+    pub ended_timestamp: i64,   //unix time the sales end at instead of ended_slot, 0 if unset size:8
+    pub start_slot: u64,        //no sales before this slot, 0 to open at init size:8
+    pub start_timestamp: i64,   //no sales before this unix time, 0 to open at init size:8
+    pub series: Pubkey,         //series this lottery is a round of, default for a one-shot lottery size:32
+    pub rollover_window: i64,   //seconds winners have to claim before the pot can roll over, 0 if rollover is off size:8
+    pub drawn_timestamp: i64,   //unix time the winning numbers were drawn at size:8
+    pub rollover_to: Pubkey,    //lottery the pot rolled over into, default until it did size:32
+    pub rollover_in: u64,       //token amount rolled over into the vault from earlier lotteries size:8
+    pub rollover_out: u64,      //token amount rolled over into rollover_to size:8
+    pub pending_authority: Pubkey, //authority proposed by SetAuthority, takes over once it accepts, default if none size:32
+                                //103 reserved bytes follow
+                                //Lottery account size should be 559 Bytes
+}
+/// Template of recurring lotteries, every round is a lottery account paid from one vault
+pub struct Series {
+    pub account_type: AccountType, //size:1
+    pub authority: Pubkey,      //authority of every round size:32
+    pub token_mint: Pubkey,     //size:32
+    pub token_reciever: Pubkey, //vault shared by the rounds, owned by the PDA of "lottery", series id size:32
+    pub fee_reciever: Pubkey,   //size:32
+    pub max_amount: u64,        //size:8
+    pub slot: u64,              //how many slots a round lasts size:8
+    pub duration: i64,          //how many seconds a round lasts instead of slot, 0 if unset size:8
+    pub prize_count: u8,        //size:1
+    pub prize_shares: [u16; MAX_PRIZES], //size:10
+    pub min_amount: u64,        //size:8
+    pub ticket_price: u64,      //size:8
+    pub max_per_wallet: u64,    //size:8
+    pub crank_reward: u64,      //size:8
+    pub round: u64,             //rounds started so far, index of the next round size:8
+    pub current_round: Pubkey,  //lottery id of the latest round, default before the first size:32
+    pub bump: u8,               //bump of the vault PDA size:1
+    pub rollover_window: i64,   //rollover window of every round, 0 if rollover is off size:8
+                                //56 reserved bytes follow
+                                //Series account size should be 301 Bytes
+}
+pub struct Config {
+    pub account_type: AccountType, //size:1
+    pub admin: Pubkey,          //size:32
+    pub fee_bps: u16,           //protocol fee in basis points size:2
+    pub fee_receiver: Pubkey,   //wallet owning the fee ATAs size:32
+    pub max_fee_bps: u16,       //fee_bps can never be set above this size:2
+    pub bump: u8,               //size:1
+                                //Config account size should be 70 Bytes
+}
+pub struct BuyerRecord {
+    pub account_type: AccountType, //size:1
+    pub lottery_id: Pubkey,     //size:32
+    pub buyer: Pubkey,          //size:32
+    pub purchased_amount: u64,  //tickets bought across every Buy size:8
+    pub bump: u8,               //size:1
+    pub ticket_count: u64,      //Buy calls so far, index of the next ticket PDA size:8
+                                //BuyerRecord account size should be 82 Bytes
 }
 pub struct Ticket {
-    pub account_type: u8,   //2 is Ticket size:1
+    pub account_type: AccountType, //size:1
     pub lottery_id: Pubkey, //size:32
     pub buyer: Pubkey,      //size:32
     pub start_number: u64,  //size:8
     pub end_number: u64,    //size:32
+    pub version: u8,        //layout version, 0 for legacy accounts size:1
+    pub status: TicketStatus, //size:1
+                            //31 reserved bytes follow
+                            //Ticket account size should be 114 Bytes
+}
+
+impl AccountType {
+    pub fn from_u8(value: u8) -> Result<Self, ProgramError> {
+        match value {
+            0 => Ok(AccountType::Uninitialized),
+            1 => Ok(AccountType::Lottery),
+            2 => Ok(AccountType::Ticket),
+            7 => Ok(AccountType::Config),
+            8 => Ok(AccountType::BuyerRecord),
+            9 => Ok(AccountType::Series),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+impl LotteryStatus {
+    pub fn from_u8(value: u8) -> Result<Self, ProgramError> {
+        match value {
+            0 => Ok(LotteryStatus::Uninitialized),
+            1 => Ok(LotteryStatus::Open),
+            3 => Ok(LotteryStatus::Drawn),
+            4 => Ok(LotteryStatus::Settled),
+            5 => Ok(LotteryStatus::AwaitingReveal),
+            6 => Ok(LotteryStatus::Cancelled),
+            7 => Ok(LotteryStatus::SoldOut),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+    /// Every lifecycle edge a lottery can take, status changes go through `Lottery::set_status`
+    pub fn can_become(self, next: LotteryStatus) -> bool {
+        use LotteryStatus::*;
+        matches!(
+            (self, next),
+            (Uninitialized, Open)
+                | (Open, AwaitingReveal)
+                | (Open, Drawn)
+                | (Open, Cancelled)
+                | (Open, SoldOut)
+                | (SoldOut, AwaitingReveal)
+                | (SoldOut, Drawn)
+                | (AwaitingReveal, Drawn)
+                | (AwaitingReveal, Cancelled)
+                | (Drawn, Settled)
+                | (Cancelled, Settled)
+        )
+    }
+}
 
-                            //Ticket account size should be 81 Bytes
+impl TicketStatus {
+    pub fn from_u8(value: u8) -> Result<Self, ProgramError> {
+        match value {
+            0 => Ok(TicketStatus::Active),
+            1 => Ok(TicketStatus::Closed),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+impl Sealed for Config {}
+
+impl IsInitialized for Config {
+    fn is_initialized(&self) -> bool {
+        self.account_type == AccountType::Config
+    }
+}
+
+impl Pack for Config {
+    const LEN: usize = 70;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Config::LEN];
+        let (_account_type, _admin, _fee_bps, _fee_receiver, _max_fee_bps, _bump) =
+            array_refs![src, 1, 32, 2, 32, 2, 1];
+
+        Ok(Config {
+            account_type: AccountType::from_u8(_account_type[0])?,
+            admin: Pubkey::new(_admin),
+            fee_bps: u16::from_le_bytes(*_fee_bps),
+            fee_receiver: Pubkey::new(_fee_receiver),
+            max_fee_bps: u16::from_le_bytes(*_max_fee_bps),
+            bump: _bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Config::LEN];
+        let (
+            _account_type_dst,
+            _admin_dst,
+            _fee_bps_dst,
+            _fee_receiver_dst,
+            _max_fee_bps_dst,
+            _bump_dst,
+        ) = mut_array_refs![dst, 1, 32, 2, 32, 2, 1];
+
+        let Config {
+            account_type,
+            admin,
+            fee_bps,
+            fee_receiver,
+            max_fee_bps,
+            bump,
+        } = self;
+        _account_type_dst[0] = *account_type as u8;
+        _admin_dst.copy_from_slice(admin.as_ref());
+        *_fee_bps_dst = fee_bps.to_le_bytes();
+        _fee_receiver_dst.copy_from_slice(fee_receiver.as_ref());
+        *_max_fee_bps_dst = max_fee_bps.to_le_bytes();
+        _bump_dst[0] = *bump;
+    }
+}
+
+impl Sealed for Series {}
+
+impl IsInitialized for Series {
+    fn is_initialized(&self) -> bool {
+        self.account_type == AccountType::Series
+    }
+}
+
+impl Pack for Series {
+    const LEN: usize = 301;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Series::LEN];
+        let (
+            _account_type,
+            _authority,
+            _token_mint,
+            _token_reciever,
+            _fee_reciever,
+            _max_amount,
+            _slot,
+            _duration,
+            _prize_count,
+            _prize_shares,
+            _min_amount,
+            _ticket_price,
+            _max_per_wallet,
+            _crank_reward,
+            _round,
+            _current_round,
+            _bump,
+            _rollover_window,
+            _reserved,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 8, 8, 8, 1, 10, 8, 8, 8, 8, 8, 32, 1, 8, 56];
+
+        let mut prize_shares = [0u16; MAX_PRIZES];
+        for (share, src) in prize_shares.iter_mut().zip(_prize_shares.chunks_exact(2)) {
+            *share = u16::from_le_bytes(src.try_into().unwrap());
+        }
+        Ok(Series {
+            account_type: AccountType::from_u8(_account_type[0])?,
+            authority: Pubkey::new(_authority),
+            token_mint: Pubkey::new(_token_mint),
+            token_reciever: Pubkey::new(_token_reciever),
+            fee_reciever: Pubkey::new(_fee_reciever),
+            max_amount: u64::from_le_bytes(*_max_amount),
+            slot: u64::from_le_bytes(*_slot),
+            duration: i64::from_le_bytes(*_duration),
+            prize_count: _prize_count[0],
+            prize_shares,
+            min_amount: u64::from_le_bytes(*_min_amount),
+            ticket_price: u64::from_le_bytes(*_ticket_price),
+            max_per_wallet: u64::from_le_bytes(*_max_per_wallet),
+            crank_reward: u64::from_le_bytes(*_crank_reward),
+            round: u64::from_le_bytes(*_round),
+            current_round: Pubkey::new(_current_round),
+            bump: _bump[0],
+            rollover_window: i64::from_le_bytes(*_rollover_window),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Series::LEN];
+        let (
+            _account_type_dst,
+            _authority_dst,
+            _token_mint_dst,
+            _token_reciever_dst,
+            _fee_reciever_dst,
+            _max_amount_dst,
+            _slot_dst,
+            _duration_dst,
+            _prize_count_dst,
+            _prize_shares_dst,
+            _min_amount_dst,
+            _ticket_price_dst,
+            _max_per_wallet_dst,
+            _crank_reward_dst,
+            _round_dst,
+            _current_round_dst,
+            _bump_dst,
+            _rollover_window_dst,
+            _reserved_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 8, 8, 8, 1, 10, 8, 8, 8, 8, 8, 32, 1, 8, 56];
+
+        let Series {
+            account_type,
+            authority,
+            token_mint,
+            token_reciever,
+            fee_reciever,
+            max_amount,
+            slot,
+            duration,
+            prize_count,
+            prize_shares,
+            min_amount,
+            ticket_price,
+            max_per_wallet,
+            crank_reward,
+            round,
+            current_round,
+            bump,
+            rollover_window,
+        } = self;
+        _account_type_dst[0] = *account_type as u8;
+        _authority_dst.copy_from_slice(authority.as_ref());
+        _token_mint_dst.copy_from_slice(token_mint.as_ref());
+        _token_reciever_dst.copy_from_slice(token_reciever.as_ref());
+        _fee_reciever_dst.copy_from_slice(fee_reciever.as_ref());
+        *_max_amount_dst = max_amount.to_le_bytes();
+        *_slot_dst = slot.to_le_bytes();
+        *_duration_dst = duration.to_le_bytes();
+        _prize_count_dst[0] = *prize_count;
+        for (dst, share) in _prize_shares_dst.chunks_exact_mut(2).zip(prize_shares.iter()) {
+            dst.copy_from_slice(&share.to_le_bytes());
+        }
+        *_min_amount_dst = min_amount.to_le_bytes();
+        *_ticket_price_dst = ticket_price.to_le_bytes();
+        *_max_per_wallet_dst = max_per_wallet.to_le_bytes();
+        *_crank_reward_dst = crank_reward.to_le_bytes();
+        *_round_dst = round.to_le_bytes();
+        _current_round_dst.copy_from_slice(current_round.as_ref());
+        _bump_dst[0] = *bump;
+        *_rollover_window_dst = rollover_window.to_le_bytes();
+    }
+}
+
+impl Sealed for BuyerRecord {}
+
+impl IsInitialized for BuyerRecord {
+    fn is_initialized(&self) -> bool {
+        self.account_type == AccountType::BuyerRecord
+    }
+}
+
+impl Pack for BuyerRecord {
+    const LEN: usize = 82;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, BuyerRecord::LEN];
+        let (_account_type, _lottery_id, _buyer, _purchased_amount, _bump, _ticket_count) =
+            array_refs![src, 1, 32, 32, 8, 1, 8];
+
+        Ok(BuyerRecord {
+            account_type: AccountType::from_u8(_account_type[0])?,
+            lottery_id: Pubkey::new(_lottery_id),
+            buyer: Pubkey::new(_buyer),
+            purchased_amount: u64::from_le_bytes(*_purchased_amount),
+            bump: _bump[0],
+            ticket_count: u64::from_le_bytes(*_ticket_count),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, BuyerRecord::LEN];
+        let (
+            _account_type_dst,
+            _lottery_id_dst,
+            _buyer_dst,
+            _purchased_amount_dst,
+            _bump_dst,
+            _ticket_count_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 8, 1, 8];
+
+        let BuyerRecord {
+            account_type,
+            lottery_id,
+            buyer,
+            purchased_amount,
+            bump,
+            ticket_count,
+        } = self;
+        _account_type_dst[0] = *account_type as u8;
+        _lottery_id_dst.copy_from_slice(lottery_id.as_ref());
+        _buyer_dst.copy_from_slice(buyer.as_ref());
+        *_purchased_amount_dst = purchased_amount.to_le_bytes();
+        _bump_dst[0] = *bump;
+        *_ticket_count_dst = ticket_count.to_le_bytes();
+    }
+}
+
+impl Ticket {
+    /// Unpacks the current layout, or a legacy 81 byte account as version 0
+    pub fn unpack_versioned(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() != LEGACY_TICKET_LEN {
+            return Ticket::unpack_unchecked(src);
+        }
+        let src = array_ref![src, 0, LEGACY_TICKET_LEN];
+        let (_account_type, _lottery_id, _buyer, _start_number, _end_number) =
+            array_refs![src, 1, 32, 32, 8, 8];
+        Ok(Ticket {
+            account_type: AccountType::from_u8(_account_type[0])?,
+            lottery_id: Pubkey::new(_lottery_id),
+            buyer: Pubkey::new(_buyer),
+            start_number: u64::from_le_bytes(*_start_number),
+            end_number: u64::from_le_bytes(*_end_number),
+            version: 0,
+            status: TicketStatus::Active,
+        })
+    }
 }
 
 impl Sealed for Ticket {}
 
 impl IsInitialized for Ticket {
     fn is_initialized(&self) -> bool {
-        self.account_type == 2
+        self.account_type == AccountType::Ticket
     }
 }
 
 impl Pack for Ticket {
-    const LEN: usize = 81;
+    const LEN: usize = 114;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, Ticket::LEN];
-        let (_account_type, _lottery_id, _buyer, _start_number, _end_number) =
-            array_refs![src, 1, 32, 32, 8, 8];
+        let (
+            _account_type,
+            _lottery_id,
+            _buyer,
+            _start_number,
+            _end_number,
+            _version,
+            _status,
+            _reserved,
+        ) = array_refs![src, 1, 32, 32, 8, 8, 1, 1, 31];
 
-        let account_type = u8::from_le_bytes(*_account_type);
+        let account_type = AccountType::from_u8(_account_type[0])?;
 
         let lottery_id = Pubkey::new(_lottery_id);
         let buyer = Pubkey::new(_buyer);
         let start_number = u64::from_le_bytes(*_start_number);
         let end_number = u64::from_le_bytes(*_end_number);
+        let version = _version[0];
+        let status = TicketStatus::from_u8(_status[0])?;
 
         Ok(Ticket {
             account_type,
@@ -55,12 +503,22 @@ impl Pack for Ticket {
             buyer,
             start_number,
             end_number,
+            version,
+            status,
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, Ticket::LEN];
-        let (_account_type_dst, _lottery_id_dst, _buyer_dst, _start_number_dst, _end_number_dst) =
-            mut_array_refs![dst, 1, 32, 32, 8, 8];
+        let (
+            _account_type_dst,
+            _lottery_id_dst,
+            _buyer_dst,
+            _start_number_dst,
+            _end_number_dst,
+            _version_dst,
+            _status_dst,
+            _reserved_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 8, 8, 1, 1, 31];
 
         let Ticket {
             account_type,
@@ -68,12 +526,134 @@ impl Pack for Ticket {
             buyer,
             start_number,
             end_number,
+            version,
+            status,
         } = self;
         _account_type_dst[0] = *account_type as u8;
         _lottery_id_dst.copy_from_slice(lottery_id.as_ref());
         _buyer_dst.copy_from_slice(buyer.as_ref());
         *_start_number_dst = start_number.to_le_bytes();
         *_end_number_dst = end_number.to_le_bytes();
+        _version_dst[0] = *version;
+        _status_dst[0] = *status as u8;
+    }
+}
+
+impl Lottery {
+    /// Unpacks the current layout, or a legacy 161 byte account as version 0 with a single
+    /// prize tier and one token per ticket number like the program used to sell them
+    pub fn unpack_versioned(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() != LEGACY_LOTTERY_LEN {
+            return Lottery::unpack(src);
+        }
+        let src = array_ref![src, 0, LEGACY_LOTTERY_LEN];
+        let (
+            _account_type,
+            _authority,
+            _token_reciever,
+            _fee_reciever,
+            _max_amount,
+            _ended_slot,
+            _lottery_number,
+            _current_amount,
+            _token_mint,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 8, 8, 32];
+        let status = LotteryStatus::from_u8(_account_type[0])?;
+        let lottery_number = u64::from_le_bytes(*_lottery_number);
+        let mut prize_shares = [0u16; MAX_PRIZES];
+        prize_shares[0] = PRIZE_SHARE_TOTAL;
+        let mut winning_numbers = [0u64; MAX_PRIZES];
+        winning_numbers[0] = lottery_number;
+        Ok(Lottery {
+            account_type: AccountType::Lottery,
+            authority: Pubkey::new(_authority),
+            token_reciever: Pubkey::new(_token_reciever),
+            fee_reciever: Pubkey::new(_fee_reciever),
+            max_amount: u64::from_le_bytes(*_max_amount),
+            ended_slot: u64::from_le_bytes(*_ended_slot),
+            lottery_number,
+            current_amount: u64::from_le_bytes(*_current_amount),
+            token_mint: Pubkey::new(_token_mint),
+            commitment: [0; 32],
+            closed_slot: 0,
+            prize_count: 1,
+            prize_shares,
+            winning_numbers,
+            paid_prizes: if status == LotteryStatus::Settled { 1 } else { 0 },
+            prize_pool: 0,
+            min_amount: 0,
+            refunded_amount: 0,
+            ticket_price: 1,
+            max_per_wallet: 0,
+            crank_reward: 0,
+            bump: 0,
+            version: 0,
+            status,
+            ended_timestamp: 0,
+            start_slot: 0,
+            start_timestamp: 0,
+            series: Pubkey::default(),
+            rollover_window: 0,
+            drawn_timestamp: 0,
+            rollover_to: Pubkey::default(),
+            rollover_in: 0,
+            rollover_out: 0,
+            pending_authority: Pubkey::default(),
+        })
+    }
+    /// Seed of the PDA owning the vault, the series for a round and the lottery itself otherwise
+    pub fn vault_id(&self, lottery_id: &Pubkey) -> Pubkey {
+        if self.series == Pubkey::default() {
+            *lottery_id
+        } else {
+            self.series
+        }
+    }
+    /// Tickets are paid in lamports held by the vault PDA itself
+    pub fn is_native(&self) -> bool {
+        is_native_mint(&self.token_mint)
+    }
+    /// Sales open once both the start slot and the start timestamp have been reached
+    pub fn has_started(&self, clock: &Clock) -> bool {
+        self.start_slot <= clock.slot && self.start_timestamp <= clock.unix_timestamp
+    }
+    /// Winners of a drawn lottery can claim until the rollover window after the draw has passed
+    pub fn claim_window_open(&self, clock: &Clock) -> bool {
+        self.current_amount != 0
+            && self.drawn_timestamp.saturating_add(self.rollover_window) >= clock.unix_timestamp
+    }
+    /// Sales are over once the ended slot or, when set, the end timestamp has passed
+    pub fn has_ended(&self, clock: &Clock) -> bool {
+        self.ended_slot < clock.slot
+            || (self.ended_timestamp != 0 && self.ended_timestamp < clock.unix_timestamp)
+    }
+    /// Slot whose hash a committed lottery is drawn with, the first one at or after it if skipped
+    pub fn reveal_slot(&self) -> u64 {
+        self.closed_slot.saturating_add(REVEAL_SLOT_OFFSET)
+    }
+    /// A committed lottery not revealed by then can be cancelled and refunded
+    pub fn reveal_deadline(&self) -> u64 {
+        self.reveal_slot().saturating_add(REVEAL_WINDOW)
+    }
+    /// Moves the lottery to `next` if its lifecycle allows it
+    pub fn set_status(&mut self, next: LotteryStatus) -> Result<(), ProgramError> {
+        if !self.status.can_become(next) {
+            return Err(LotteryError::InvalidStatusTransition.into());
+        }
+        self.status = next;
+        Ok(())
+    }
+    /// Amount of tier `index` out of the pot, the last unpaid tier takes whatever is left
+    pub fn prize_amount(&self, index: usize, vault_balance: u64) -> u64 {
+        let unpaid = (0..self.prize_count as usize)
+            .filter(|i| self.paid_prizes & (1 << i) == 0)
+            .count();
+        if unpaid <= 1 {
+            return vault_balance;
+        }
+        let amount = self.prize_pool as u128 * self.prize_shares[index] as u128
+            / PRIZE_SHARE_TOTAL as u128;
+        std::cmp::min(amount as u64, vault_balance)
     }
 }
 
@@ -81,12 +661,12 @@ impl Sealed for Lottery {}
 
 impl IsInitialized for Lottery {
     fn is_initialized(&self) -> bool {
-        self.account_type != 0
+        self.account_type == AccountType::Lottery
     }
 }
 
 impl Pack for Lottery {
-    const LEN: usize = 161;
+    const LEN: usize = 559;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, Lottery::LEN];
         let (
@@ -98,10 +678,34 @@ impl Pack for Lottery {
             _ended_slot,
             _lottery_number,
             _current_amount,
-            _token_mint
-        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 8, 8,32];
-
-        let account_type = u8::from_le_bytes(*_account_type);
+            _token_mint,
+            _commitment,
+            _closed_slot,
+            _prize_count,
+            _prize_shares,
+            _winning_numbers,
+            _paid_prizes,
+            _prize_pool,
+            _min_amount,
+            _refunded_amount,
+            _ticket_price,
+            _max_per_wallet,
+            _crank_reward,
+            _bump,
+            _version,
+            _status,
+            _ended_timestamp,
+            _start_slot,
+            _start_timestamp,
+            _series,
+            _rollover_window,
+            _drawn_timestamp,
+            _rollover_to,
+            _rollover_in,
+            _rollover_out,
+            _pending_authority,
+            _reserved,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 8, 8, 32, 32, 8, 1, 10, 40, 1, 8, 8, 8, 8, 8, 8, 1, 1, 1, 8, 8, 8, 32, 8, 8, 32, 8, 8, 32, 103];
 
         let authority = Pubkey::new(_authority);
         let token_reciever = Pubkey::new(_token_reciever);
@@ -111,6 +715,38 @@ impl Pack for Lottery {
         let ended_slot = u64::from_le_bytes(*_ended_slot);
         let lottery_number = u64::from_le_bytes(*_lottery_number);
         let current_amount = u64::from_le_bytes(*_current_amount);
+        let commitment = *_commitment;
+        let closed_slot = u64::from_le_bytes(*_closed_slot);
+        let prize_count = _prize_count[0];
+        let mut prize_shares = [0u16; MAX_PRIZES];
+        let mut winning_numbers = [0u64; MAX_PRIZES];
+        for (share, src) in prize_shares.iter_mut().zip(_prize_shares.chunks_exact(2)) {
+            *share = u16::from_le_bytes(src.try_into().unwrap());
+        }
+        for (number, src) in winning_numbers.iter_mut().zip(_winning_numbers.chunks_exact(8)) {
+            *number = u64::from_le_bytes(src.try_into().unwrap());
+        }
+        let paid_prizes = _paid_prizes[0];
+        let prize_pool = u64::from_le_bytes(*_prize_pool);
+        let min_amount = u64::from_le_bytes(*_min_amount);
+        let refunded_amount = u64::from_le_bytes(*_refunded_amount);
+        let ticket_price = u64::from_le_bytes(*_ticket_price);
+        let max_per_wallet = u64::from_le_bytes(*_max_per_wallet);
+        let crank_reward = u64::from_le_bytes(*_crank_reward);
+        let bump = _bump[0];
+        let ended_timestamp = i64::from_le_bytes(*_ended_timestamp);
+        let start_slot = u64::from_le_bytes(*_start_slot);
+        let start_timestamp = i64::from_le_bytes(*_start_timestamp);
+        let series = Pubkey::new(_series);
+        let rollover_window = i64::from_le_bytes(*_rollover_window);
+        let drawn_timestamp = i64::from_le_bytes(*_drawn_timestamp);
+        let rollover_to = Pubkey::new(_rollover_to);
+        let rollover_in = u64::from_le_bytes(*_rollover_in);
+        let rollover_out = u64::from_le_bytes(*_rollover_out);
+        let pending_authority = Pubkey::new(_pending_authority);
+        let version = _version[0];
+        let account_type = AccountType::from_u8(_account_type[0])?;
+        let status = LotteryStatus::from_u8(_status[0])?;
         Ok(Lottery {
             account_type,
             authority,
@@ -120,7 +756,32 @@ impl Pack for Lottery {
             ended_slot,
             lottery_number,
             current_amount,
-            token_mint
+            token_mint,
+            commitment,
+            closed_slot,
+            prize_count,
+            prize_shares,
+            winning_numbers,
+            paid_prizes,
+            prize_pool,
+            min_amount,
+            refunded_amount,
+            ticket_price,
+            max_per_wallet,
+            crank_reward,
+            bump,
+            version,
+            status,
+            ended_timestamp,
+            start_slot,
+            start_timestamp,
+            series,
+            rollover_window,
+            drawn_timestamp,
+            rollover_to,
+            rollover_in,
+            rollover_out,
+            pending_authority,
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
@@ -135,8 +796,34 @@ impl Pack for Lottery {
             _ended_slot_dst,
             _lottery_number_dst,
             _current_amount_dst,
-            _token_mint_dst
-        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 8, 8,32];
+            _token_mint_dst,
+            _commitment_dst,
+            _closed_slot_dst,
+            _prize_count_dst,
+            _prize_shares_dst,
+            _winning_numbers_dst,
+            _paid_prizes_dst,
+            _prize_pool_dst,
+            _min_amount_dst,
+            _refunded_amount_dst,
+            _ticket_price_dst,
+            _max_per_wallet_dst,
+            _crank_reward_dst,
+            _bump_dst,
+            _version_dst,
+            _status_dst,
+            _ended_timestamp_dst,
+            _start_slot_dst,
+            _start_timestamp_dst,
+            _series_dst,
+            _rollover_window_dst,
+            _drawn_timestamp_dst,
+            _rollover_to_dst,
+            _rollover_in_dst,
+            _rollover_out_dst,
+            _pending_authority_dst,
+            _reserved_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 8, 8, 32, 32, 8, 1, 10, 40, 1, 8, 8, 8, 8, 8, 8, 1, 1, 1, 8, 8, 8, 32, 8, 8, 32, 8, 8, 32, 103];
 
         let Lottery {
             account_type,
@@ -148,6 +835,31 @@ impl Pack for Lottery {
             lottery_number,
             current_amount,
             token_mint,
+            commitment,
+            closed_slot,
+            prize_count,
+            prize_shares,
+            winning_numbers,
+            paid_prizes,
+            prize_pool,
+            min_amount,
+            refunded_amount,
+            ticket_price,
+            max_per_wallet,
+            crank_reward,
+            bump,
+            version,
+            status,
+            ended_timestamp,
+            start_slot,
+            start_timestamp,
+            series,
+            rollover_window,
+            drawn_timestamp,
+            rollover_to,
+            rollover_in,
+            rollover_out,
+            pending_authority,
         } = self;
         _account_type_dst[0] = *account_type as u8;
         _authority_dst.copy_from_slice(authority.as_ref());
@@ -158,5 +870,34 @@ impl Pack for Lottery {
         *_ended_slot_dst = ended_slot.to_le_bytes();
         *_lottery_number_dst = lottery_number.to_le_bytes();
         *_current_amount_dst = current_amount.to_le_bytes();
+        _commitment_dst.copy_from_slice(commitment);
+        *_closed_slot_dst = closed_slot.to_le_bytes();
+        _prize_count_dst[0] = *prize_count;
+        for (dst, share) in _prize_shares_dst.chunks_exact_mut(2).zip(prize_shares.iter()) {
+            dst.copy_from_slice(&share.to_le_bytes());
+        }
+        for (dst, number) in _winning_numbers_dst.chunks_exact_mut(8).zip(winning_numbers.iter()) {
+            dst.copy_from_slice(&number.to_le_bytes());
+        }
+        _paid_prizes_dst[0] = *paid_prizes;
+        *_prize_pool_dst = prize_pool.to_le_bytes();
+        *_min_amount_dst = min_amount.to_le_bytes();
+        *_refunded_amount_dst = refunded_amount.to_le_bytes();
+        *_ticket_price_dst = ticket_price.to_le_bytes();
+        *_max_per_wallet_dst = max_per_wallet.to_le_bytes();
+        *_crank_reward_dst = crank_reward.to_le_bytes();
+        _bump_dst[0] = *bump;
+        _version_dst[0] = *version;
+        _status_dst[0] = *status as u8;
+        *_ended_timestamp_dst = ended_timestamp.to_le_bytes();
+        *_start_slot_dst = start_slot.to_le_bytes();
+        *_start_timestamp_dst = start_timestamp.to_le_bytes();
+        _series_dst.copy_from_slice(series.as_ref());
+        *_rollover_window_dst = rollover_window.to_le_bytes();
+        *_drawn_timestamp_dst = drawn_timestamp.to_le_bytes();
+        _rollover_to_dst.copy_from_slice(rollover_to.as_ref());
+        *_rollover_in_dst = rollover_in.to_le_bytes();
+        *_rollover_out_dst = rollover_out.to_le_bytes();
+        _pending_authority_dst.copy_from_slice(pending_authority.as_ref());
     }
 }
@@ -0,0 +1,109 @@
+use lottery_mechine::event::{LotteryEvent, EVENT_SCHEMA_VERSION};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+fn all_events() -> Vec<LotteryEvent> {
+    let lottery_id = Pubkey::new_unique();
+    vec![
+        LotteryEvent::LotteryCreated {
+            lottery_id,
+            authority: Pubkey::new_unique(),
+            token_mint: Pubkey::new_unique(),
+            max_amount: 100,
+            ended_slot: 1_000,
+            ticket_price: 10,
+            ended_timestamp: 1_700_000_000,
+            start_slot: 500,
+            start_timestamp: 1_600_000_000,
+        },
+        LotteryEvent::TicketBought {
+            lottery_id,
+            ticket_id: Pubkey::new_unique(),
+            buyer: Pubkey::new_unique(),
+            start_number: 1,
+            end_number: 5,
+            cost: 50,
+        },
+        LotteryEvent::Drawn {
+            lottery_id,
+            winning_numbers: vec![3, 7],
+        },
+        LotteryEvent::PrizePaid {
+            lottery_id,
+            prize_index: 1,
+            winner: Pubkey::new_unique(),
+            amount: 40,
+            fee: 2,
+        },
+        LotteryEvent::TicketClosed {
+            lottery_id,
+            ticket_id: Pubkey::new_unique(),
+            buyer: Pubkey::new_unique(),
+            refunded: 0,
+        },
+        LotteryEvent::RolledOver {
+            lottery_id,
+            successor: Pubkey::new_unique(),
+            amount: 90,
+        },
+        LotteryEvent::AuthorityChanged {
+            lottery_id,
+            old_authority: Pubkey::new_unique(),
+            new_authority: Pubkey::new_unique(),
+        },
+        LotteryEvent::LotteryUpdated {
+            lottery_id,
+            max_amount: 200,
+            ended_slot: 2_000,
+            ended_timestamp: 0,
+        },
+        LotteryEvent::LotteryCancelled { lottery_id, sold: 3 },
+        LotteryEvent::AwaitingReveal {
+            lottery_id,
+            reveal_slot: 1_010,
+            reveal_deadline: 1_266,
+        },
+        LotteryEvent::CrankRewardPaid {
+            lottery_id,
+            caller: Pubkey::new_unique(),
+            amount: 1,
+        },
+    ]
+}
+
+#[test]
+fn test_round_trip() {
+    for event in all_events() {
+        let data = event.pack();
+        assert_eq!(data[0], EVENT_SCHEMA_VERSION);
+        assert_eq!(LotteryEvent::unpack(&data).unwrap(), event);
+    }
+}
+
+#[test]
+fn test_trailing_bytes() {
+    for event in all_events() {
+        let mut data = event.pack();
+        data.push(0);
+        assert_eq!(LotteryEvent::unpack(&data), Err(ProgramError::InvalidArgument));
+    }
+}
+
+#[test]
+fn test_truncated_event() {
+    for event in all_events() {
+        let data = event.pack();
+        assert_eq!(
+            LotteryEvent::unpack(&data[..data.len() - 1]),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+}
+
+#[test]
+fn test_unknown_version() {
+    for event in all_events() {
+        let mut data = event.pack();
+        data[0] = EVENT_SCHEMA_VERSION + 1;
+        assert_eq!(LotteryEvent::unpack(&data), Err(ProgramError::InvalidArgument));
+    }
+}
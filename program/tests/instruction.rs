@@ -0,0 +1,134 @@
+use assert_matches::assert_matches;
+use lottery_mechine::{error::LotteryError, instruction::LotteryMachineInstructions};
+use solana_program::program_error::ProgramError;
+
+fn all_instructions() -> Vec<LotteryMachineInstructions> {
+    vec![
+        LotteryMachineInstructions::InitLottery {
+            max_amount: 100,
+            slot: 1_000,
+            commitment: [7; 32],
+            prize_shares: vec![5_000, 3_000, 2_000],
+            min_amount: 10,
+            ticket_price: 1_000_000,
+            max_per_wallet: 5,
+            crank_reward: 42,
+            end_timestamp: 1_700_000_000,
+            start_slot: 500,
+            start_timestamp: 1_600_000_000,
+            rollover_window: 3_600,
+        },
+        LotteryMachineInstructions::Buy {
+            amount: 3,
+            partial: true,
+        },
+        LotteryMachineInstructions::Draw {},
+        LotteryMachineInstructions::Withdraw { prize_index: 2 },
+        LotteryMachineInstructions::Close {},
+        LotteryMachineInstructions::Reveal { seed: [9; 32] },
+        LotteryMachineInstructions::Refund {},
+        LotteryMachineInstructions::InitConfig {
+            fee_bps: 500,
+            max_fee_bps: 1_000,
+        },
+        LotteryMachineInstructions::SetConfig {
+            fee_bps: 250,
+            max_fee_bps: 500,
+        },
+        LotteryMachineInstructions::Claim { prize_index: 1 },
+        LotteryMachineInstructions::Migrate {},
+        LotteryMachineInstructions::InitSeries {
+            max_amount: 100,
+            slot: 0,
+            prize_shares: vec![],
+            min_amount: 0,
+            ticket_price: 10,
+            max_per_wallet: 0,
+            crank_reward: 0,
+            duration: 86_400,
+            rollover_window: 0,
+        },
+        LotteryMachineInstructions::NextRound {},
+        LotteryMachineInstructions::Rollover {},
+        LotteryMachineInstructions::SetAuthority { propose: true },
+        LotteryMachineInstructions::AcceptAuthority {},
+        LotteryMachineInstructions::UpdateLottery {
+            max_amount: 200,
+            ended_slot: 5_000,
+            ended_timestamp: 0,
+        },
+    ]
+}
+
+fn assert_invalid(input: &[u8]) {
+    assert_matches!(
+        LotteryMachineInstructions::unpack(input),
+        Err(ProgramError::Custom(code)) if code == LotteryError::InvalidInstruction as u32
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    for ix in all_instructions() {
+        let data = ix.pack();
+        assert_eq!(LotteryMachineInstructions::unpack(&data).unwrap(), ix);
+    }
+}
+
+#[test]
+fn test_tags_and_buy_layout() {
+    for (tag, ix) in all_instructions().iter().enumerate() {
+        assert_eq!(ix.pack()[0], tag as u8);
+    }
+    let mut data = vec![1];
+    data.extend_from_slice(&3u64.to_le_bytes());
+    data.push(1);
+    assert_eq!(
+        LotteryMachineInstructions::Buy {
+            amount: 3,
+            partial: true,
+        }
+        .pack(),
+        data
+    );
+}
+
+#[test]
+fn test_empty_input() {
+    assert_invalid(&[]);
+}
+
+#[test]
+fn test_unknown_tag() {
+    assert_invalid(&[17]);
+    assert_invalid(&[255, 0, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_truncated_input() {
+    for ix in all_instructions() {
+        let data = ix.pack();
+        for len in 1..data.len() {
+            assert_invalid(&data[..len]);
+        }
+    }
+}
+
+#[test]
+fn test_trailing_bytes() {
+    for ix in all_instructions() {
+        let mut data = ix.pack();
+        data.push(0);
+        assert_invalid(&data);
+    }
+}
+
+#[test]
+fn test_oversized_prize_count() {
+    let mut data = vec![0];
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&[0; 32]);
+    data.extend_from_slice(&u32::MAX.to_le_bytes());
+    assert_invalid(&data);
+}
@@ -0,0 +1,2004 @@
+use assert_matches::assert_matches;
+use lottery_mechine::{
+    error::LotteryError,
+    find_lottery_address, find_round_address, find_ticket_address, id,
+    instruction,
+    processor::Processor,
+    state::{
+        Lottery, LotteryStatus, Series, Ticket, LEGACY_LOTTERY_LEN, LOTTERY_VERSION,
+        REVEAL_SLOT_OFFSET, REVEAL_WINDOW,
+    },
+};
+use solana_program::{
+    clock::Clock,
+    hash::hash,
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+const FEE_BPS: u16 = 500;
+
+struct TestLottery {
+    context: ProgramTestContext,
+    mint: Pubkey,
+    fee_receiver: Pubkey,
+    lottery_id: Keypair,
+}
+
+async fn start() -> ProgramTestContext {
+    ProgramTest::new("lottery_mechine", id(), processor!(Processor::process))
+        .start_with_context()
+        .await
+}
+
+async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransportError> {
+    let mut all_signers: Vec<&Keypair> = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+fn assert_lottery_error(result: Result<(), TransportError>, error: LotteryError) {
+    assert_matches!(
+        result,
+        Err(TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code)
+        ))) if code == error as u32
+    );
+}
+
+async fn get_lottery(context: &mut ProgramTestContext, lottery_id: &Pubkey) -> Lottery {
+    let account = context.banks_client.get_account(*lottery_id).await.unwrap().unwrap();
+    Lottery::unpack(&account.data).unwrap()
+}
+
+async fn token_balance(context: &mut ProgramTestContext, token_account: &Pubkey) -> u64 {
+    let account = context.banks_client.get_account(*token_account).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+fn init_config_ix(admin: &Pubkey, fee_receiver: &Pubkey) -> Instruction {
+    instruction::init_config(&id(), admin, fee_receiver, FEE_BPS, FEE_BPS)
+}
+
+/// One prize tier, no commitment, no min amount, no wallet cap and no crank reward,
+/// the lottery PDA and fee ATA can be swapped for the rejection tests
+#[allow(clippy::too_many_arguments)]
+fn init_lottery_ix(
+    lottery_id: &Pubkey,
+    authority: &Pubkey,
+    fee_receiver: &Pubkey,
+    lottery_pda: &Pubkey,
+    fee_ata: &Pubkey,
+    mint: &Pubkey,
+    max_amount: u64,
+    slot: u64,
+) -> Instruction {
+    let mut init = instruction::init_lottery(
+        &id(),
+        lottery_id,
+        authority,
+        fee_receiver,
+        mint,
+        max_amount,
+        slot,
+        [0; 32],
+        vec![],
+        0,
+        1,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    init.accounts[3].pubkey = *lottery_pda;
+    init.accounts[5].pubkey = *fee_ata;
+    init
+}
+
+fn buy_ix(lottery_id: &Pubkey, mint: &Pubkey, buyer: &Pubkey, index: u64, amount: u64) -> Instruction {
+    let lottery_pda = find_lottery_address(lottery_id, &id()).0;
+    instruction::buy(
+        &id(),
+        lottery_id,
+        buyer,
+        &get_associated_token_address(buyer, mint),
+        &get_associated_token_address(&lottery_pda, mint),
+        mint,
+        index,
+        amount,
+        false,
+    )
+}
+
+fn draw_ix(lottery_id: &Pubkey, caller: &Pubkey) -> Instruction {
+    instruction::draw(&id(), lottery_id, caller)
+}
+
+fn withdraw_ix(
+    lottery_id: &Pubkey,
+    authority: &Pubkey,
+    fee_ata: &Pubkey,
+    winner: &Pubkey,
+    winning_ticket: &Pubkey,
+    mint: &Pubkey,
+) -> Instruction {
+    let lottery_pda = find_lottery_address(lottery_id, &id()).0;
+    instruction::withdraw(
+        &id(),
+        lottery_id,
+        lottery_id,
+        authority,
+        &get_associated_token_address(&lottery_pda, mint),
+        fee_ata,
+        winner,
+        winning_ticket,
+        mint,
+        0,
+    )
+}
+
+fn close_ix(lottery_id: &Pubkey, ticket_id: &Pubkey, buyer: &Pubkey) -> Instruction {
+    instruction::close(&id(), lottery_id, ticket_id, buyer)
+}
+
+/// Config, a mint whose authority is the payer and a lottery of `max_amount` tickets
+async fn setup(max_amount: u64, slot: u64) -> TestLottery {
+    let mut context = start().await;
+    let payer = context.payer.pubkey();
+    let fee_receiver = Keypair::new().pubkey();
+    process(&mut context, &[init_config_ix(&payer, &fee_receiver)], &[])
+        .await
+        .unwrap();
+
+    let mint = Keypair::new();
+    let mint_rent = context.banks_client.get_rent().await.unwrap().minimum_balance(Mint::LEN);
+    let create_mint = vec![
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            mint_rent,
+            Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), &payer, None, 0)
+            .unwrap(),
+    ];
+    process(&mut context, &create_mint, &[&mint]).await.unwrap();
+
+    let lottery_id = Keypair::new();
+    let lottery_pda = find_lottery_address(&lottery_id.pubkey(), &id()).0;
+    let fee_ata = get_associated_token_address(&fee_receiver, &mint.pubkey());
+    let init = init_lottery_ix(
+        &lottery_id.pubkey(),
+        &payer,
+        &fee_receiver,
+        &lottery_pda,
+        &fee_ata,
+        &mint.pubkey(),
+        max_amount,
+        slot,
+    );
+    process(&mut context, &[init], &[&lottery_id]).await.unwrap();
+
+    TestLottery {
+        context,
+        mint: mint.pubkey(),
+        fee_receiver,
+        lottery_id,
+    }
+}
+
+/// A funded buyer holding `tokens` of the lottery mint
+async fn new_buyer(test: &mut TestLottery, tokens: u64) -> Keypair {
+    let buyer = Keypair::new();
+    let payer = test.context.payer.pubkey();
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    let fund = vec![
+        system_instruction::transfer(&payer, &buyer.pubkey(), 1_000_000_000),
+        create_associated_token_account(&payer, &buyer.pubkey(), &test.mint),
+        spl_token::instruction::mint_to(&spl_token::id(), &test.mint, &buyer_ata, &payer, &[], tokens)
+            .unwrap(),
+    ];
+    process(&mut test.context, &fund, &[]).await.unwrap();
+    buyer
+}
+
+fn fee_ata(test: &TestLottery) -> Pubkey {
+    get_associated_token_address(&test.fee_receiver, &test.mint)
+}
+
+/// A 10 ticket lottery selling between the timestamps, the zero slot duration is ignored
+fn timed_lottery_ix(
+    test: &TestLottery,
+    lottery_id: &Pubkey,
+    start_timestamp: i64,
+    end_timestamp: i64,
+) -> Instruction {
+    let payer = test.context.payer.pubkey();
+    instruction::init_lottery(
+        &id(),
+        lottery_id,
+        &payer,
+        &test.fee_receiver,
+        &test.mint,
+        10,
+        0,
+        [0; 32],
+        vec![],
+        0,
+        1,
+        0,
+        0,
+        end_timestamp,
+        0,
+        start_timestamp,
+        0,
+    )
+}
+
+/// A 10 ticket lottery lasting `slot` slots whose pot can roll over an hour after the draw
+fn rollover_lottery_ix(test: &TestLottery, lottery_id: &Pubkey, slot: u64) -> Instruction {
+    let payer = test.context.payer.pubkey();
+    instruction::init_lottery(
+        &id(),
+        lottery_id,
+        &payer,
+        &test.fee_receiver,
+        &test.mint,
+        10,
+        slot,
+        [0; 32],
+        vec![],
+        0,
+        1,
+        0,
+        0,
+        0,
+        0,
+        0,
+        3600,
+    )
+}
+
+/// A 10 ticket lottery lasting 100 slots that commits to the hash of `seed` and pays
+/// `crank_reward` to whoever reveals it
+fn committed_lottery_ix(
+    test: &TestLottery,
+    lottery_id: &Pubkey,
+    seed: &[u8; 32],
+    crank_reward: u64,
+) -> Instruction {
+    let payer = test.context.payer.pubkey();
+    instruction::init_lottery(
+        &id(),
+        lottery_id,
+        &payer,
+        &test.fee_receiver,
+        &test.mint,
+        10,
+        100,
+        hash(seed).to_bytes(),
+        vec![],
+        0,
+        1,
+        0,
+        crank_reward,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+/// A 10 ticket lottery lasting 100 slots with the given prize tiers, min amount, wallet cap
+/// and crank reward
+fn tiered_lottery_ix(
+    test: &TestLottery,
+    lottery_id: &Pubkey,
+    prize_shares: Vec<u16>,
+    min_amount: u64,
+    max_per_wallet: u64,
+    crank_reward: u64,
+) -> Instruction {
+    let payer = test.context.payer.pubkey();
+    instruction::init_lottery(
+        &id(),
+        lottery_id,
+        &payer,
+        &test.fee_receiver,
+        &test.mint,
+        10,
+        100,
+        [0; 32],
+        prize_shares,
+        min_amount,
+        1,
+        max_per_wallet,
+        crank_reward,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn reveal_ix(lottery_id: &Pubkey, caller: &Pubkey, seed: [u8; 32]) -> Instruction {
+    instruction::reveal(&id(), lottery_id, caller, seed, None)
+}
+
+fn refund_ix(test: &TestLottery, lottery_id: &Pubkey, buyer: &Pubkey) -> Instruction {
+    instruction::refund(
+        &id(),
+        lottery_id,
+        lottery_id,
+        &find_ticket_address(lottery_id, buyer, 0, &id()).0,
+        buyer,
+        &get_associated_token_address(buyer, &test.mint),
+        &vault(test, lottery_id),
+        &test.mint,
+        &test.context.payer.pubkey(),
+    )
+}
+
+fn vault(test: &TestLottery, lottery_id: &Pubkey) -> Pubkey {
+    get_associated_token_address(&find_lottery_address(lottery_id, &id()).0, &test.mint)
+}
+
+fn rollover_ix(test: &TestLottery, lottery_id: &Pubkey, successor_id: &Pubkey) -> Instruction {
+    instruction::rollover(
+        &id(),
+        lottery_id,
+        lottery_id,
+        &test.context.payer.pubkey(),
+        &vault(test, lottery_id),
+        &test.mint,
+        successor_id,
+        &vault(test, successor_id),
+    )
+}
+
+#[tokio::test]
+async fn test_happy_path() {
+    let mut test = setup(100, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 100).await;
+
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Open);
+    assert_eq!(lottery_info.max_amount, 100);
+
+    process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 100)],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+    let ticket_id = find_ticket_address(&lottery_id, &buyer.pubkey(), 0, &id()).0;
+    let ticket_account = test.context.banks_client.get_account(ticket_id).await.unwrap().unwrap();
+    let ticket_info = Ticket::unpack(&ticket_account.data).unwrap();
+    assert_eq!(ticket_info.start_number, 1);
+    assert_eq!(ticket_info.end_number, 100);
+    assert_eq!(ticket_info.buyer, buyer.pubkey());
+
+    // sold out, so it can be drawn before the ended slot
+    let payer = test.context.payer.pubkey();
+    process(&mut test.context, &[draw_ix(&lottery_id, &payer)], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Drawn);
+    assert!(lottery_info.winning_numbers[0] >= 1 && lottery_info.winning_numbers[0] <= 100);
+
+    let withdraw = withdraw_ix(
+        &lottery_id,
+        &payer,
+        &fee_ata(&test),
+        &buyer.pubkey(),
+        &ticket_id,
+        &test.mint,
+    );
+    process(&mut test.context, &[withdraw], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Settled);
+    let fee = 100 * FEE_BPS as u64 / 10000;
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 100 - fee);
+    let fee_account = fee_ata(&test);
+    assert_eq!(token_balance(&mut test.context, &fee_account).await, fee);
+
+    let buyer_lamports = test.context.banks_client.get_balance(buyer.pubkey()).await.unwrap();
+    let ticket_lamports = test.context.banks_client.get_balance(ticket_id).await.unwrap();
+    process(
+        &mut test.context,
+        &[close_ix(&lottery_id, &ticket_id, &buyer.pubkey())],
+        &[],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        test.context.banks_client.get_balance(buyer.pubkey()).await.unwrap(),
+        buyer_lamports + ticket_lamports
+    );
+    assert!(test.context.banks_client.get_account(ticket_id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_init_wrong_pda() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let lottery_id = Keypair::new();
+    // the PDA of another lottery
+    let wrong_pda = find_lottery_address(&test.lottery_id.pubkey(), &id()).0;
+    let init = init_lottery_ix(
+        &lottery_id.pubkey(),
+        &payer,
+        &test.fee_receiver,
+        &wrong_pda,
+        &fee_ata(&test),
+        &test.mint,
+        10,
+        100,
+    );
+    let result = process(&mut test.context, &[init], &[&lottery_id]).await;
+    assert_lottery_error(result, LotteryError::WrongPda);
+}
+
+#[tokio::test]
+async fn test_init_wrong_fee_account() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let lottery_id = Keypair::new();
+    let lottery_pda = find_lottery_address(&lottery_id.pubkey(), &id()).0;
+    let wrong_fee_ata = get_associated_token_address(&payer, &test.mint);
+    let init = init_lottery_ix(
+        &lottery_id.pubkey(),
+        &payer,
+        &test.fee_receiver,
+        &lottery_pda,
+        &wrong_fee_ata,
+        &test.mint,
+        10,
+        100,
+    );
+    let result = process(&mut test.context, &[init], &[&lottery_id]).await;
+    assert_lottery_error(result, LotteryError::WrongFeeAccount);
+
+    // a fee reciever other than the one in the config
+    let init = init_lottery_ix(
+        &lottery_id.pubkey(),
+        &payer,
+        &payer,
+        &lottery_pda,
+        &wrong_fee_ata,
+        &test.mint,
+        10,
+        100,
+    );
+    let result = process(&mut test.context, &[init], &[&lottery_id]).await;
+    assert_lottery_error(result, LotteryError::WrongFeeAccount);
+}
+
+#[tokio::test]
+async fn test_init_authority_not_signer() {
+    let mut test = setup(10, 100).await;
+    let authority = Keypair::new().pubkey();
+    let lottery_id = Keypair::new();
+    let lottery_pda = find_lottery_address(&lottery_id.pubkey(), &id()).0;
+    let mut init = init_lottery_ix(
+        &lottery_id.pubkey(),
+        &authority,
+        &test.fee_receiver,
+        &lottery_pda,
+        &fee_ata(&test),
+        &test.mint,
+        10,
+        100,
+    );
+    init.accounts[1].is_signer = false;
+    let result = process(&mut test.context, &[init], &[&lottery_id]).await;
+    assert_matches!(
+        result,
+        Err(TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::MissingRequiredSignature
+        )))
+    );
+    assert!(test
+        .context
+        .banks_client
+        .get_account(lottery_id.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_buy_ended_lottery() {
+    let mut test = setup(10, 5).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 10).await;
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    test.context.warp_to_slot(lottery_info.ended_slot + 10).unwrap();
+
+    let result = process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 1)],
+        &[&buyer],
+    )
+    .await;
+    assert_lottery_error(result, LotteryError::LotteryEnded);
+}
+
+#[tokio::test]
+async fn test_buy_buyer_not_signer() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 10).await;
+    let mut buy = buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 1);
+    buy.accounts[2].is_signer = false;
+
+    let result = process(&mut test.context, &[buy], &[]).await;
+    assert!(result.is_err());
+    let ticket_id = find_ticket_address(&lottery_id, &buyer.pubkey(), 0, &id()).0;
+    assert!(test.context.banks_client.get_account(ticket_id).await.unwrap().is_none());
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 10);
+}
+
+#[tokio::test]
+async fn test_buy_wrong_ticket() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 10).await;
+    // the first purchase has to use index 0
+    let result = process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 1, 1)],
+        &[&buyer],
+    )
+    .await;
+    assert_lottery_error(result, LotteryError::WrongTicket);
+}
+
+#[tokio::test]
+async fn test_draw_before_end() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 10).await;
+    process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 5)],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+
+    let payer = test.context.payer.pubkey();
+    let result = process(&mut test.context, &[draw_ix(&lottery_id, &payer)], &[]).await;
+    assert_lottery_error(result, LotteryError::LotteryNotEnded);
+}
+
+#[tokio::test]
+async fn test_draw_caller_not_signer() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let caller = Keypair::new();
+    let mut draw = draw_ix(&lottery_id, &caller.pubkey());
+    draw.accounts[1].is_signer = false;
+    let result = process(&mut test.context, &[draw], &[]).await;
+    assert_matches!(
+        result,
+        Err(TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::MissingRequiredSignature
+        )))
+    );
+}
+
+#[tokio::test]
+async fn test_draw_wrong_slot_hashes() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let payer = test.context.payer.pubkey();
+    let mut draw = draw_ix(&lottery_id, &payer);
+    draw.accounts[3].pubkey = Keypair::new().pubkey();
+    let result = process(&mut test.context, &[draw], &[]).await;
+    assert_lottery_error(result, LotteryError::WrongSysvar);
+}
+
+#[tokio::test]
+async fn test_init_past_end_timestamp() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = Keypair::new();
+    let clock = test.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let init = timed_lottery_ix(&test, &lottery_id.pubkey(), 0, clock.unix_timestamp);
+    let result = process(&mut test.context, &[init], &[&lottery_id]).await;
+    assert_lottery_error(result, LotteryError::InvalidEndTimestamp);
+}
+
+#[tokio::test]
+async fn test_draw_before_end_timestamp() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = Keypair::new();
+    let clock = test.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let init = timed_lottery_ix(&test, &lottery_id.pubkey(), 0, clock.unix_timestamp + 3600);
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id.pubkey()).await;
+    assert_eq!(lottery_info.ended_timestamp, clock.unix_timestamp + 3600);
+
+    // past the slot duration, still open until the timestamp
+    test.context.warp_to_slot(clock.slot + 10).unwrap();
+    let buyer = new_buyer(&mut test, 10).await;
+    process(
+        &mut test.context,
+        &[buy_ix(&lottery_id.pubkey(), &test.mint, &buyer.pubkey(), 0, 5)],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+
+    let payer = test.context.payer.pubkey();
+    let result = process(&mut test.context, &[draw_ix(&lottery_id.pubkey(), &payer)], &[]).await;
+    assert_lottery_error(result, LotteryError::LotteryNotEnded);
+}
+
+#[tokio::test]
+async fn test_init_start_after_end() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = Keypair::new();
+    let clock = test.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let end = clock.unix_timestamp + 3600;
+    let init = timed_lottery_ix(&test, &lottery_id.pubkey(), end, end);
+    let result = process(&mut test.context, &[init], &[&lottery_id]).await;
+    assert_lottery_error(result, LotteryError::InvalidStartTime);
+}
+
+#[tokio::test]
+async fn test_buy_before_start() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = Keypair::new();
+    let clock = test.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let init = timed_lottery_ix(
+        &test,
+        &lottery_id.pubkey(),
+        clock.unix_timestamp + 3600,
+        clock.unix_timestamp + 7200,
+    );
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+
+    let buyer = new_buyer(&mut test, 10).await;
+    let result = process(
+        &mut test.context,
+        &[buy_ix(&lottery_id.pubkey(), &test.mint, &buyer.pubkey(), 0, 1)],
+        &[&buyer],
+    )
+    .await;
+    assert_lottery_error(result, LotteryError::LotteryNotStarted);
+}
+
+/// Two buyers splitting the lottery, returns their tickets with the winner first
+async fn drawn_lottery(test: &mut TestLottery) -> ((Keypair, Pubkey), (Keypair, Pubkey)) {
+    let lottery_id = test.lottery_id.pubkey();
+    let first = new_buyer(test, 5).await;
+    let second = new_buyer(test, 5).await;
+    for buyer in [&first, &second] {
+        process(
+            &mut test.context,
+            &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 5)],
+            &[buyer],
+        )
+        .await
+        .unwrap();
+    }
+    let payer = test.context.payer.pubkey();
+    process(&mut test.context, &[draw_ix(&lottery_id, &payer)], &[]).await.unwrap();
+
+    let first_ticket = find_ticket_address(&lottery_id, &first.pubkey(), 0, &id()).0;
+    let second_ticket = find_ticket_address(&lottery_id, &second.pubkey(), 0, &id()).0;
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    if lottery_info.winning_numbers[0] <= 5 {
+        ((first, first_ticket), (second, second_ticket))
+    } else {
+        ((second, second_ticket), (first, first_ticket))
+    }
+}
+
+#[tokio::test]
+async fn test_withdraw_wrong_winner() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let (_, (loser, loser_ticket)) = drawn_lottery(&mut test).await;
+
+    let payer = test.context.payer.pubkey();
+    let withdraw = withdraw_ix(
+        &lottery_id,
+        &payer,
+        &fee_ata(&test),
+        &loser.pubkey(),
+        &loser_ticket,
+        &test.mint,
+    );
+    let result = process(&mut test.context, &[withdraw], &[]).await;
+    assert_lottery_error(result, LotteryError::WrongWinner);
+}
+
+#[tokio::test]
+async fn test_withdraw_to_foreign_account() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let ((winner, winner_ticket), (loser, _)) = drawn_lottery(&mut test).await;
+
+    // the winning ticket doesn't let the authority pick where the prize goes
+    let payer = test.context.payer.pubkey();
+    let mut withdraw = withdraw_ix(
+        &lottery_id,
+        &payer,
+        &fee_ata(&test),
+        &winner.pubkey(),
+        &winner_ticket,
+        &test.mint,
+    );
+    withdraw.accounts[4].pubkey = get_associated_token_address(&loser.pubkey(), &test.mint);
+    let result = process(&mut test.context, &[withdraw], &[]).await;
+    assert_lottery_error(result, LotteryError::WrongWinner);
+}
+
+#[tokio::test]
+async fn test_withdraw_wrong_fee_account() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let ((winner, winner_ticket), _) = drawn_lottery(&mut test).await;
+
+    let payer = test.context.payer.pubkey();
+    let wrong_fee_ata = get_associated_token_address(&winner.pubkey(), &test.mint);
+    let withdraw = withdraw_ix(
+        &lottery_id,
+        &payer,
+        &wrong_fee_ata,
+        &winner.pubkey(),
+        &winner_ticket,
+        &test.mint,
+    );
+    let result = process(&mut test.context, &[withdraw], &[]).await;
+    assert_lottery_error(result, LotteryError::WrongFeeAccount);
+}
+
+#[tokio::test]
+async fn test_withdraw_wrong_authority() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let ((winner, winner_ticket), _) = drawn_lottery(&mut test).await;
+
+    let withdraw = withdraw_ix(
+        &lottery_id,
+        &winner.pubkey(),
+        &fee_ata(&test),
+        &winner.pubkey(),
+        &winner_ticket,
+        &test.mint,
+    );
+    let result = process(&mut test.context, &[withdraw], &[&winner]).await;
+    assert_lottery_error(result, LotteryError::WrongAuthority);
+}
+
+#[tokio::test]
+async fn test_withdraw_wrong_pda() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let ((winner, winner_ticket), _) = drawn_lottery(&mut test).await;
+
+    let payer = test.context.payer.pubkey();
+    let mut withdraw = withdraw_ix(
+        &lottery_id,
+        &payer,
+        &fee_ata(&test),
+        &winner.pubkey(),
+        &winner_ticket,
+        &test.mint,
+    );
+    withdraw.accounts[6].pubkey = find_lottery_address(&Keypair::new().pubkey(), &id()).0;
+    let result = process(&mut test.context, &[withdraw], &[]).await;
+    assert_lottery_error(result, LotteryError::WrongPda);
+}
+
+#[tokio::test]
+async fn test_close_before_settled() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let ((winner, winner_ticket), _) = drawn_lottery(&mut test).await;
+
+    let result = process(
+        &mut test.context,
+        &[close_ix(&lottery_id, &winner_ticket, &winner.pubkey())],
+        &[],
+    )
+    .await;
+    assert_lottery_error(result, LotteryError::NotSettled);
+}
+
+#[tokio::test]
+async fn test_close_read_only_buyer() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let ((winner, winner_ticket), (loser, loser_ticket)) = drawn_lottery(&mut test).await;
+    let payer = test.context.payer.pubkey();
+    let withdraw = withdraw_ix(
+        &lottery_id,
+        &payer,
+        &fee_ata(&test),
+        &winner.pubkey(),
+        &winner_ticket,
+        &test.mint,
+    );
+    process(&mut test.context, &[withdraw], &[]).await.unwrap();
+
+    // the ticket is writable but the buyer getting its rent isn't
+    let mut close = close_ix(&lottery_id, &loser_ticket, &loser.pubkey());
+    close.accounts[2].is_writable = false;
+    let result = process(&mut test.context, &[close], &[]).await;
+    assert_lottery_error(result, LotteryError::AccountNotWritable);
+}
+
+#[tokio::test]
+async fn test_series_rounds() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let series_id = Keypair::new();
+    let vault = get_associated_token_address(
+        &find_lottery_address(&series_id.pubkey(), &id()).0,
+        &test.mint,
+    );
+    let init = instruction::init_series(
+        &id(),
+        &series_id.pubkey(),
+        &payer,
+        &test.fee_receiver,
+        &test.mint,
+        2,
+        100,
+        vec![],
+        0,
+        1,
+        0,
+        0,
+        0,
+        0,
+    );
+    process(&mut test.context, &[init], &[&series_id]).await.unwrap();
+
+    let next_round = instruction::next_round(&id(), &series_id.pubkey(), &payer, 0);
+    process(&mut test.context, &[next_round], &[]).await.unwrap();
+    let round_id = find_round_address(&series_id.pubkey(), 0, &id()).0;
+    let round_info = get_lottery(&mut test.context, &round_id).await;
+    assert_eq!(round_info.status, LotteryStatus::Open);
+    assert_eq!(round_info.series, series_id.pubkey());
+    assert_eq!(round_info.token_reciever, vault);
+    assert_eq!(round_info.max_amount, 2);
+
+    // the first round has to settle before the next one opens
+    let buyer = new_buyer(&mut test, 2).await;
+    let next_round = instruction::next_round(&id(), &series_id.pubkey(), &buyer.pubkey(), 1);
+    let result = process(&mut test.context, &[next_round], &[&buyer]).await;
+    assert_lottery_error(result, LotteryError::RoundNotSettled);
+
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    let buy = instruction::buy(
+        &id(),
+        &round_id,
+        &buyer.pubkey(),
+        &buyer_ata,
+        &vault,
+        &test.mint,
+        0,
+        2,
+        false,
+    );
+    process(&mut test.context, &[buy], &[&buyer]).await.unwrap();
+    process(&mut test.context, &[draw_ix(&round_id, &payer)], &[]).await.unwrap();
+    let ticket_id = find_ticket_address(&round_id, &buyer.pubkey(), 0, &id()).0;
+    let withdraw = instruction::withdraw(
+        &id(),
+        &round_id,
+        &series_id.pubkey(),
+        &payer,
+        &vault,
+        &fee_ata(&test),
+        &buyer.pubkey(),
+        &ticket_id,
+        &test.mint,
+        0,
+    );
+    process(&mut test.context, &[withdraw], &[]).await.unwrap();
+    let round_info = get_lottery(&mut test.context, &round_id).await;
+    assert_eq!(round_info.status, LotteryStatus::Settled);
+    let fee = 2 * FEE_BPS as u64 / 10000;
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 2 - fee);
+    // the vault is kept for the next round
+    assert_eq!(token_balance(&mut test.context, &vault).await, 0);
+
+    let next_round = instruction::next_round(&id(), &series_id.pubkey(), &payer, 1);
+    process(&mut test.context, &[next_round], &[]).await.unwrap();
+    let next_round_id = find_round_address(&series_id.pubkey(), 1, &id()).0;
+    let next_round_info = get_lottery(&mut test.context, &next_round_id).await;
+    assert_eq!(next_round_info.status, LotteryStatus::Open);
+    assert_eq!(next_round_info.token_reciever, vault);
+    let series_account = test
+        .context
+        .banks_client
+        .get_account(series_id.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let series_info = Series::unpack(&series_account.data).unwrap();
+    assert_eq!(series_info.round, 2);
+    assert_eq!(series_info.current_round, next_round_id);
+}
+
+#[tokio::test]
+async fn test_series_unsold_round() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let series_id = Keypair::new();
+    let vault = vault(&test, &series_id.pubkey());
+    let init = instruction::init_series(
+        &id(),
+        &series_id.pubkey(),
+        &payer,
+        &test.fee_receiver,
+        &test.mint,
+        2,
+        5,
+        vec![],
+        0,
+        1,
+        0,
+        0,
+        0,
+        0,
+    );
+    let next_round = instruction::next_round(&id(), &series_id.pubkey(), &payer, 0);
+    process(&mut test.context, &[init, next_round], &[&series_id]).await.unwrap();
+    let round_id = find_round_address(&series_id.pubkey(), 0, &id()).0;
+
+    // a pot left in the vault nobody bought into
+    let mint_to = spl_token::instruction::mint_to(&spl_token::id(), &test.mint, &vault, &payer, &[], 7)
+        .unwrap();
+    process(&mut test.context, &[mint_to], &[]).await.unwrap();
+    let round_info = get_lottery(&mut test.context, &round_id).await;
+    test.context.warp_to_slot(round_info.ended_slot + 10).unwrap();
+    process(&mut test.context, &[draw_ix(&round_id, &payer)], &[]).await.unwrap();
+
+    let ticket_id = find_ticket_address(&round_id, &payer, 0, &id()).0;
+    let withdraw = instruction::withdraw(
+        &id(),
+        &round_id,
+        &series_id.pubkey(),
+        &payer,
+        &vault,
+        &fee_ata(&test),
+        &payer,
+        &ticket_id,
+        &test.mint,
+        0,
+    );
+    let result = process(&mut test.context, &[withdraw], &[]).await;
+    assert_lottery_error(result, LotteryError::RolloverRequired);
+
+    // the series has no rollover window, the unsold round still hands its pot on
+    let rollover = instruction::rollover(
+        &id(),
+        &round_id,
+        &series_id.pubkey(),
+        &payer,
+        &vault,
+        &test.mint,
+        &series_id.pubkey(),
+        &vault,
+    );
+    let next_round = instruction::next_round(&id(), &series_id.pubkey(), &payer, 1);
+    process(&mut test.context, &[rollover, next_round], &[]).await.unwrap();
+    let next_round_id = find_round_address(&series_id.pubkey(), 1, &id()).0;
+    let round_info = get_lottery(&mut test.context, &round_id).await;
+    assert_eq!(round_info.status, LotteryStatus::Settled);
+    assert_eq!(round_info.rollover_to, next_round_id);
+    assert_eq!(round_info.rollover_out, 7);
+    let next_round_info = get_lottery(&mut test.context, &next_round_id).await;
+    assert_eq!(next_round_info.rollover_in, 7);
+
+    // the next round sells and its winner takes the carried pot as well
+    let buyer = new_buyer(&mut test, 2).await;
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    let buy = instruction::buy(
+        &id(),
+        &next_round_id,
+        &buyer.pubkey(),
+        &buyer_ata,
+        &vault,
+        &test.mint,
+        0,
+        2,
+        false,
+    );
+    process(&mut test.context, &[buy], &[&buyer]).await.unwrap();
+    test.context.warp_to_slot(next_round_info.ended_slot + 10).unwrap();
+    process(&mut test.context, &[draw_ix(&next_round_id, &payer)], &[])
+        .await
+        .unwrap();
+    let ticket_id = find_ticket_address(&next_round_id, &buyer.pubkey(), 0, &id()).0;
+    let withdraw = instruction::withdraw(
+        &id(),
+        &next_round_id,
+        &series_id.pubkey(),
+        &payer,
+        &vault,
+        &fee_ata(&test),
+        &buyer.pubkey(),
+        &ticket_id,
+        &test.mint,
+        0,
+    );
+    process(&mut test.context, &[withdraw], &[]).await.unwrap();
+    let fee = 9 * FEE_BPS as u64 / 10000;
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 9 - fee);
+    assert_eq!(token_balance(&mut test.context, &vault).await, 0);
+}
+
+#[tokio::test]
+async fn test_rollover_unsold() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let unsold = Keypair::new();
+    let successor = Keypair::new();
+    let init = [
+        rollover_lottery_ix(&test, &unsold.pubkey(), 1),
+        rollover_lottery_ix(&test, &successor.pubkey(), 100),
+    ];
+    process(&mut test.context, &init, &[&unsold, &successor])
+    .await
+    .unwrap();
+
+    // the setup lottery was created without a rollover window
+    let rollover = rollover_ix(&test, &test.lottery_id.pubkey(), &successor.pubkey());
+    let result = process(&mut test.context, &[rollover], &[]).await;
+    assert_lottery_error(result, LotteryError::RolloverDisabled);
+
+    // a pot carried over from an earlier lottery
+    let unsold_vault = vault(&test, &unsold.pubkey());
+    let mint_to =
+        spl_token::instruction::mint_to(&spl_token::id(), &test.mint, &unsold_vault, &payer, &[], 7)
+            .unwrap();
+    process(&mut test.context, &[mint_to], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &unsold.pubkey()).await;
+    test.context.warp_to_slot(lottery_info.ended_slot + 10).unwrap();
+    process(&mut test.context, &[draw_ix(&unsold.pubkey(), &payer)], &[])
+        .await
+        .unwrap();
+
+    let rollover = rollover_ix(&test, &unsold.pubkey(), &successor.pubkey());
+    process(&mut test.context, &[rollover], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &unsold.pubkey()).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Settled);
+    assert_eq!(lottery_info.rollover_to, successor.pubkey());
+    assert_eq!(lottery_info.rollover_out, 7);
+    let successor_info = get_lottery(&mut test.context, &successor.pubkey()).await;
+    assert_eq!(successor_info.rollover_in, 7);
+    let successor_vault = vault(&test, &successor.pubkey());
+    assert_eq!(token_balance(&mut test.context, &successor_vault).await, 7);
+    // the emptied vault is closed
+    let unsold_vault_account = test.context.banks_client.get_account(unsold_vault).await.unwrap();
+    assert!(unsold_vault_account.is_none());
+}
+
+#[tokio::test]
+async fn test_withdraw_rolled_over_jackpot() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let unsold = Keypair::new();
+    let successor = Keypair::new();
+    let init = [
+        rollover_lottery_ix(&test, &unsold.pubkey(), 1),
+        rollover_lottery_ix(&test, &successor.pubkey(), 100),
+    ];
+    process(&mut test.context, &init, &[&unsold, &successor])
+    .await
+    .unwrap();
+    let unsold_vault = vault(&test, &unsold.pubkey());
+    let mint_to =
+        spl_token::instruction::mint_to(&spl_token::id(), &test.mint, &unsold_vault, &payer, &[], 7)
+            .unwrap();
+    process(&mut test.context, &[mint_to], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &unsold.pubkey()).await;
+    test.context.warp_to_slot(lottery_info.ended_slot + 10).unwrap();
+    let draw = draw_ix(&unsold.pubkey(), &payer);
+    let rollover = rollover_ix(&test, &unsold.pubkey(), &successor.pubkey());
+    process(&mut test.context, &[draw, rollover], &[]).await.unwrap();
+
+    // nobody bought into the successor either
+    let successor_info = get_lottery(&mut test.context, &successor.pubkey()).await;
+    assert_eq!(successor_info.rollover_in, 7);
+    test.context.warp_to_slot(successor_info.ended_slot + 10).unwrap();
+    process(&mut test.context, &[draw_ix(&successor.pubkey(), &payer)], &[])
+        .await
+        .unwrap();
+
+    let ticket_id = find_ticket_address(&successor.pubkey(), &payer, 0, &id()).0;
+    let withdraw = withdraw_ix(
+        &successor.pubkey(),
+        &payer,
+        &fee_ata(&test),
+        &payer,
+        &ticket_id,
+        &test.mint,
+    );
+    let result = process(&mut test.context, &[withdraw], &[]).await;
+    assert_lottery_error(result, LotteryError::RolloverRequired);
+    let successor_vault = vault(&test, &successor.pubkey());
+    assert_eq!(token_balance(&mut test.context, &successor_vault).await, 7);
+}
+
+#[tokio::test]
+async fn test_series_jackpot() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let series_id = Keypair::new();
+    let vault = vault(&test, &series_id.pubkey());
+    let init = instruction::init_series(
+        &id(),
+        &series_id.pubkey(),
+        &payer,
+        &test.fee_receiver,
+        &test.mint,
+        2,
+        5,
+        vec![],
+        2,
+        1,
+        0,
+        0,
+        0,
+        3600,
+    );
+    let next_round = instruction::next_round(&id(), &series_id.pubkey(), &payer, 0);
+    process(&mut test.context, &[init, next_round], &[&series_id]).await.unwrap();
+    let round_id = find_round_address(&series_id.pubkey(), 0, &id()).0;
+    let mint = test.mint;
+    let rollover_ix = |round_id: &Pubkey| {
+        instruction::rollover(
+            &id(),
+            round_id,
+            &series_id.pubkey(),
+            &payer,
+            &vault,
+            &mint,
+            &series_id.pubkey(),
+            &vault,
+        )
+    };
+
+    // a jackpot in the vault of a round cancelled below its min amount
+    let mint_to = spl_token::instruction::mint_to(&spl_token::id(), &test.mint, &vault, &payer, &[], 7)
+        .unwrap();
+    process(&mut test.context, &[mint_to], &[]).await.unwrap();
+    let buyer = new_buyer(&mut test, 1).await;
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    let buy = instruction::buy(
+        &id(),
+        &round_id,
+        &buyer.pubkey(),
+        &buyer_ata,
+        &vault,
+        &test.mint,
+        0,
+        1,
+        false,
+    );
+    process(&mut test.context, &[buy], &[&buyer]).await.unwrap();
+    let round_info = get_lottery(&mut test.context, &round_id).await;
+    test.context.warp_to_slot(round_info.ended_slot + 10).unwrap();
+    process(&mut test.context, &[draw_ix(&round_id, &payer)], &[]).await.unwrap();
+    let refund = instruction::refund(
+        &id(),
+        &round_id,
+        &series_id.pubkey(),
+        &find_ticket_address(&round_id, &buyer.pubkey(), 0, &id()).0,
+        &buyer.pubkey(),
+        &buyer_ata,
+        &vault,
+        &test.mint,
+        &payer,
+    );
+    process(&mut test.context, &[refund], &[&buyer]).await.unwrap();
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 1);
+    // the jackpot keeps the round from settling
+    let round_info = get_lottery(&mut test.context, &round_id).await;
+    assert_eq!(round_info.status, LotteryStatus::Cancelled);
+
+    let next_round = instruction::next_round(&id(), &series_id.pubkey(), &payer, 1);
+    process(&mut test.context, &[rollover_ix(&round_id), next_round], &[])
+        .await
+        .unwrap();
+    let next_round_id = find_round_address(&series_id.pubkey(), 1, &id()).0;
+    let round_info = get_lottery(&mut test.context, &round_id).await;
+    assert_eq!(round_info.status, LotteryStatus::Settled);
+    assert_eq!(round_info.rollover_out, 7);
+    let next_round_info = get_lottery(&mut test.context, &next_round_id).await;
+    assert_eq!(next_round_info.rollover_in, 7);
+
+    // nobody buys into the next round, the authority can't take the jackpot
+    test.context.warp_to_slot(next_round_info.ended_slot + 10).unwrap();
+    process(&mut test.context, &[draw_ix(&next_round_id, &payer)], &[])
+        .await
+        .unwrap();
+    let withdraw = instruction::withdraw(
+        &id(),
+        &next_round_id,
+        &series_id.pubkey(),
+        &payer,
+        &vault,
+        &fee_ata(&test),
+        &payer,
+        &find_ticket_address(&next_round_id, &payer, 0, &id()).0,
+        &test.mint,
+        0,
+    );
+    let result = process(&mut test.context, &[withdraw], &[]).await;
+    assert_lottery_error(result, LotteryError::RolloverRequired);
+
+    let rollover = rollover_ix(&next_round_id);
+    let next_round = instruction::next_round(&id(), &series_id.pubkey(), &payer, 2);
+    process(&mut test.context, &[rollover, next_round], &[]).await.unwrap();
+    let last_round_id = find_round_address(&series_id.pubkey(), 2, &id()).0;
+    let last_round_info = get_lottery(&mut test.context, &last_round_id).await;
+    assert_eq!(last_round_info.rollover_in, 7);
+    assert_eq!(token_balance(&mut test.context, &vault).await, 7);
+}
+
+#[tokio::test]
+async fn test_rollover_during_claim_window() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let drawn = Keypair::new();
+    let successor = Keypair::new();
+    let init = [
+        rollover_lottery_ix(&test, &drawn.pubkey(), 100),
+        rollover_lottery_ix(&test, &successor.pubkey(), 100),
+    ];
+    process(&mut test.context, &init, &[&drawn, &successor])
+    .await
+    .unwrap();
+    let buyer = new_buyer(&mut test, 10).await;
+    process(
+        &mut test.context,
+        &[buy_ix(&drawn.pubkey(), &test.mint, &buyer.pubkey(), 0, 10)],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+    process(&mut test.context, &[draw_ix(&drawn.pubkey(), &payer)], &[])
+        .await
+        .unwrap();
+
+    let rollover = rollover_ix(&test, &drawn.pubkey(), &successor.pubkey());
+    let result = process(&mut test.context, &[rollover], &[]).await;
+    assert_lottery_error(result, LotteryError::PotStillClaimable);
+}
+
+#[tokio::test]
+async fn test_native_lottery() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let lottery_id = Keypair::new();
+    let native = system_program::id();
+    let ticket_price = 1_000_000_000;
+    let init = instruction::init_lottery(
+        &id(),
+        &lottery_id.pubkey(),
+        &payer,
+        &test.fee_receiver,
+        &native,
+        2,
+        100,
+        [0; 32],
+        vec![],
+        0,
+        ticket_price,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+    let vault = find_lottery_address(&lottery_id.pubkey(), &id()).0;
+    let lottery_info = get_lottery(&mut test.context, &lottery_id.pubkey()).await;
+    assert_eq!(lottery_info.token_reciever, vault);
+    assert_eq!(lottery_info.fee_reciever, test.fee_receiver);
+    let reserve = test.context.banks_client.get_rent().await.unwrap().minimum_balance(0);
+    let vault_balance = test.context.banks_client.get_balance(vault).await.unwrap();
+    assert_eq!(vault_balance, reserve);
+
+    // the buyer only holds SOL
+    let buyer = Keypair::new();
+    let fund = system_instruction::transfer(&payer, &buyer.pubkey(), 3 * ticket_price);
+    process(&mut test.context, &[fund], &[]).await.unwrap();
+    let buy = instruction::buy(
+        &id(),
+        &lottery_id.pubkey(),
+        &buyer.pubkey(),
+        &buyer.pubkey(),
+        &vault,
+        &native,
+        0,
+        2,
+        false,
+    );
+    process(&mut test.context, &[buy], &[&buyer]).await.unwrap();
+    let vault_balance = test.context.banks_client.get_balance(vault).await.unwrap();
+    assert_eq!(vault_balance, reserve + 2 * ticket_price);
+
+    process(&mut test.context, &[draw_ix(&lottery_id.pubkey(), &payer)], &[])
+        .await
+        .unwrap();
+    let ticket_id = find_ticket_address(&lottery_id.pubkey(), &buyer.pubkey(), 0, &id()).0;
+    let buyer_balance = test.context.banks_client.get_balance(buyer.pubkey()).await.unwrap();
+    let withdraw = instruction::withdraw(
+        &id(),
+        &lottery_id.pubkey(),
+        &lottery_id.pubkey(),
+        &payer,
+        &vault,
+        &test.fee_receiver,
+        &buyer.pubkey(),
+        &ticket_id,
+        &native,
+        0,
+    );
+    process(&mut test.context, &[withdraw], &[]).await.unwrap();
+
+    let fee = 2 * ticket_price * FEE_BPS as u64 / 10000;
+    let prize_balance = test.context.banks_client.get_balance(buyer.pubkey()).await.unwrap();
+    assert_eq!(prize_balance, buyer_balance + 2 * ticket_price - fee);
+    let fee_balance = test.context.banks_client.get_balance(test.fee_receiver).await.unwrap();
+    assert_eq!(fee_balance, fee);
+    let lottery_info = get_lottery(&mut test.context, &lottery_id.pubkey()).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Settled);
+    // the reserve went back to the authority with the emptied vault
+    let vault_account = test.context.banks_client.get_account(vault).await.unwrap();
+    assert!(vault_account.is_none());
+}
+
+#[tokio::test]
+async fn test_native_prize_to_empty_wallet() {
+    let mut test = setup(10, 100).await;
+    let payer = test.context.payer.pubkey();
+    let lottery_id = Keypair::new();
+    let native = system_program::id();
+    let ticket_price = 1_000;
+    let init = instruction::init_lottery(
+        &id(),
+        &lottery_id.pubkey(),
+        &payer,
+        &test.fee_receiver,
+        &native,
+        2,
+        100,
+        [0; 32],
+        vec![],
+        0,
+        ticket_price,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+    let vault = find_lottery_address(&lottery_id.pubkey(), &id()).0;
+    let reserve = test.context.banks_client.get_rent().await.unwrap().minimum_balance(0);
+
+    let buyer = Keypair::new();
+    let fund = system_instruction::transfer(&payer, &buyer.pubkey(), 1_000_000_000);
+    process(&mut test.context, &[fund], &[]).await.unwrap();
+    let buy = instruction::buy(
+        &id(),
+        &lottery_id.pubkey(),
+        &buyer.pubkey(),
+        &buyer.pubkey(),
+        &vault,
+        &native,
+        0,
+        2,
+        false,
+    );
+    process(&mut test.context, &[buy], &[&buyer]).await.unwrap();
+    // the winner wallet is emptied, and so removed, before the draw
+    let buyer_balance = test.context.banks_client.get_balance(buyer.pubkey()).await.unwrap();
+    let drain = system_instruction::transfer(&buyer.pubkey(), &payer, buyer_balance);
+    let fund_fee = system_instruction::transfer(&payer, &test.fee_receiver, reserve);
+    process(&mut test.context, &[drain, fund_fee], &[&buyer]).await.unwrap();
+    process(&mut test.context, &[draw_ix(&lottery_id.pubkey(), &payer)], &[])
+        .await
+        .unwrap();
+
+    let ticket_id = find_ticket_address(&lottery_id.pubkey(), &buyer.pubkey(), 0, &id()).0;
+    let withdraw = instruction::withdraw(
+        &id(),
+        &lottery_id.pubkey(),
+        &lottery_id.pubkey(),
+        &payer,
+        &vault,
+        &test.fee_receiver,
+        &buyer.pubkey(),
+        &ticket_id,
+        &native,
+        0,
+    );
+    let result = process(&mut test.context, std::slice::from_ref(&withdraw), &[]).await;
+    assert_lottery_error(result, LotteryError::PayoutBelowRent);
+
+    // once the wallet holds lamports the prize goes through
+    let fund_winner = system_instruction::transfer(&payer, &buyer.pubkey(), reserve);
+    process(&mut test.context, &[fund_winner, withdraw], &[]).await.unwrap();
+    let fee = 2 * ticket_price * FEE_BPS as u64 / 10000;
+    let prize_balance = test.context.banks_client.get_balance(buyer.pubkey()).await.unwrap();
+    assert_eq!(prize_balance, reserve + 2 * ticket_price - fee);
+}
+
+#[tokio::test]
+async fn test_set_authority_propose_accept() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let payer = test.context.payer.pubkey();
+    let new_authority = Keypair::new();
+
+    let propose = instruction::set_authority(&id(), &lottery_id, &payer, &new_authority.pubkey(), true);
+    process(&mut test.context, &[propose], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.authority, payer);
+    assert_eq!(lottery_info.pending_authority, new_authority.pubkey());
+
+    let stranger = Keypair::new();
+    let accept = instruction::accept_authority(&id(), &lottery_id, &stranger.pubkey());
+    let result = process(&mut test.context, &[accept], &[&stranger]).await;
+    assert_lottery_error(result, LotteryError::WrongAuthority);
+
+    let accept = instruction::accept_authority(&id(), &lottery_id, &new_authority.pubkey());
+    process(&mut test.context, &[accept], &[&new_authority]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.authority, new_authority.pubkey());
+    assert_eq!(lottery_info.pending_authority, Pubkey::default());
+
+    // the old authority is locked out, the new one can hand over directly
+    let set = instruction::set_authority(&id(), &lottery_id, &payer, &payer, false);
+    let result = process(&mut test.context, &[set], &[]).await;
+    assert_lottery_error(result, LotteryError::WrongAuthority);
+    let set = instruction::set_authority(&id(), &lottery_id, &new_authority.pubkey(), &payer, false);
+    process(&mut test.context, &[set], &[&new_authority]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.authority, payer);
+}
+
+#[tokio::test]
+async fn test_update_lottery() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let payer = test.context.payer.pubkey();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    let ended_slot = lottery_info.ended_slot;
+
+    let update = instruction::update_lottery(&id(), &lottery_id, &payer, 20, ended_slot + 50, 0);
+    process(&mut test.context, &[update], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.max_amount, 20);
+    assert_eq!(lottery_info.ended_slot, ended_slot + 50);
+
+    let shorten = instruction::update_lottery(&id(), &lottery_id, &payer, 20, ended_slot, 0);
+    let result = process(&mut test.context, &[shorten], &[]).await;
+    assert_lottery_error(result, LotteryError::LotteryShortened);
+
+    // the cap comes down to the tickets already sold but not below them
+    let buyer = new_buyer(&mut test, 5).await;
+    process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 5)],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+    let lower_cap = instruction::update_lottery(&id(), &lottery_id, &payer, 4, ended_slot + 50, 0);
+    let result = process(&mut test.context, &[lower_cap], &[]).await;
+    assert_lottery_error(result, LotteryError::LotteryShortened);
+    let lower_cap = instruction::update_lottery(&id(), &lottery_id, &payer, 15, ended_slot + 50, 0);
+    process(&mut test.context, &[lower_cap], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.max_amount, 15);
+    assert_eq!(lottery_info.status, LotteryStatus::Open);
+    let lower_cap = instruction::update_lottery(&id(), &lottery_id, &payer, 5, ended_slot + 50, 0);
+    process(&mut test.context, &[lower_cap], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.max_amount, 5);
+    assert_eq!(lottery_info.status, LotteryStatus::SoldOut);
+
+    let stranger = Keypair::new();
+    let update = instruction::update_lottery(&id(), &lottery_id, &stranger.pubkey(), 30, ended_slot + 50, 0);
+    let result = process(&mut test.context, &[update], &[&stranger]).await;
+    assert_lottery_error(result, LotteryError::WrongAuthority);
+}
+
+#[tokio::test]
+async fn test_update_timed_lottery() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = Keypair::new();
+    let payer = test.context.payer.pubkey();
+    let clock = test.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let end_timestamp = clock.unix_timestamp + 3600;
+    let init = timed_lottery_ix(&test, &lottery_id.pubkey(), 0, end_timestamp);
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+
+    // only the end time is given, the cap and the unused end slot are kept
+    let update = instruction::update_lottery(&id(), &lottery_id.pubkey(), &payer, 0, 0, end_timestamp + 600);
+    process(&mut test.context, &[update], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id.pubkey()).await;
+    assert_eq!(lottery_info.ended_timestamp, end_timestamp + 600);
+    assert_eq!(lottery_info.max_amount, 10);
+    assert_eq!(lottery_info.ended_slot, u64::MAX);
+
+    let shorten = instruction::update_lottery(&id(), &lottery_id.pubkey(), &payer, 0, 0, end_timestamp);
+    let result = process(&mut test.context, &[shorten], &[]).await;
+    assert_lottery_error(result, LotteryError::LotteryShortened);
+}
+
+#[tokio::test]
+async fn test_update_ended_lottery() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let payer = test.context.payer.pubkey();
+    let ended_slot = get_lottery(&mut test.context, &lottery_id).await.ended_slot;
+    test.context.warp_to_slot(ended_slot + 10).unwrap();
+
+    let update = instruction::update_lottery(&id(), &lottery_id, &payer, 10, ended_slot + 100, 0);
+    let result = process(&mut test.context, &[update], &[]).await;
+    assert_lottery_error(result, LotteryError::LotteryEnded);
+}
+
+#[tokio::test]
+async fn test_buy_past_cap() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 20).await;
+    process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 8)],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+
+    let result = process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 1, 5)],
+        &[&buyer],
+    )
+    .await;
+    assert_lottery_error(result, LotteryError::LotterySoldOut);
+
+    // a partial buy only takes and pays for the 2 tickets left
+    let mut partial_buy = buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 1, 5);
+    partial_buy.data = instruction::LotteryMachineInstructions::Buy {
+        amount: 5,
+        partial: true,
+    }
+    .pack();
+    process(&mut test.context, &[partial_buy], &[&buyer]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.current_amount, 10);
+    assert_eq!(lottery_info.status, LotteryStatus::SoldOut);
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 10);
+
+    let result = process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 2, 1)],
+        &[&buyer],
+    )
+    .await;
+    assert_lottery_error(result, LotteryError::LotterySoldOut);
+}
+
+/// A sold out committed lottery whose sales Draw has closed
+async fn closed_committed_lottery(test: &mut TestLottery, seed: &[u8; 32]) -> (Pubkey, Keypair) {
+    let lottery_id = Keypair::new();
+    let init = committed_lottery_ix(test, &lottery_id.pubkey(), seed, 0);
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+    let lottery_id = lottery_id.pubkey();
+    let buyer = new_buyer(test, 10).await;
+    let payer = test.context.payer.pubkey();
+    process(
+        &mut test.context,
+        &[
+            buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 10),
+            draw_ix(&lottery_id, &payer),
+        ],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+    (lottery_id, buyer)
+}
+
+#[tokio::test]
+async fn test_reveal() {
+    let mut test = setup(10, 100).await;
+    let seed = [7; 32];
+    let (lottery_id, buyer) = closed_committed_lottery(&mut test, &seed).await;
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::AwaitingReveal);
+    assert_eq!(lottery_info.reveal_slot(), lottery_info.closed_slot + REVEAL_SLOT_OFFSET);
+
+    // the hash of the reveal slot doesn't exist yet
+    let reveal = reveal_ix(&lottery_id, &buyer.pubkey(), seed);
+    let result = process(&mut test.context, &[reveal], &[&buyer]).await;
+    assert_lottery_error(result, LotteryError::SlotHashNotAvailable);
+
+    test.context.warp_to_slot(lottery_info.reveal_slot() + 1).unwrap();
+    let reveal = reveal_ix(&lottery_id, &buyer.pubkey(), [8; 32]);
+    let result = process(&mut test.context, &[reveal], &[&buyer]).await;
+    assert_lottery_error(result, LotteryError::CommitmentMismatch);
+
+    // not the authority, anyone holding the seed can reveal it
+    process(&mut test.context, &[reveal_ix(&lottery_id, &buyer.pubkey(), seed)], &[&buyer])
+        .await
+        .unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Drawn);
+    assert!(lottery_info.winning_numbers[0] >= 1 && lottery_info.winning_numbers[0] <= 10);
+}
+
+#[tokio::test]
+async fn test_reveal_missed_deadline() {
+    let mut test = setup(10, 100).await;
+    let seed = [7; 32];
+    let (lottery_id, buyer) = closed_committed_lottery(&mut test, &seed).await;
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.reveal_deadline(), lottery_info.reveal_slot() + REVEAL_WINDOW);
+
+    // the authority can't keep the lottery open by staying silent
+    let payer = test.context.payer.pubkey();
+    let result = process(&mut test.context, &[draw_ix(&lottery_id, &payer)], &[]).await;
+    assert_lottery_error(result, LotteryError::LotteryNotOpen);
+    test.context.warp_to_slot(lottery_info.reveal_deadline() + 1).unwrap();
+    let result = process(&mut test.context, &[reveal_ix(&lottery_id, &payer, seed)], &[]).await;
+    assert_lottery_error(result, LotteryError::RevealDeadlinePassed);
+
+    process(&mut test.context, &[draw_ix(&lottery_id, &payer)], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Cancelled);
+    let refund = refund_ix(&test, &lottery_id, &buyer.pubkey());
+    process(&mut test.context, &[refund], &[&buyer]).await.unwrap();
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 10);
+}
+
+#[tokio::test]
+async fn test_multi_tier_payouts() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = Keypair::new();
+    let init = tiered_lottery_ix(&test, &lottery_id.pubkey(), vec![6000, 4000], 0, 0, 0);
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+    let lottery_id = lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 10).await;
+    let payer = test.context.payer.pubkey();
+    process(
+        &mut test.context,
+        &[
+            buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 10),
+            draw_ix(&lottery_id, &payer),
+        ],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.prize_count, 2);
+
+    // the only buyer holds every number, so wins both tiers
+    let ticket_id = find_ticket_address(&lottery_id, &buyer.pubkey(), 0, &id()).0;
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    let withdraw = withdraw_ix(
+        &lottery_id,
+        &payer,
+        &fee_ata(&test),
+        &buyer.pubkey(),
+        &ticket_id,
+        &test.mint,
+    );
+    process(&mut test.context, &[withdraw], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 6);
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Drawn);
+    assert_eq!(lottery_info.paid_prizes, 1);
+    let lottery_pda = find_lottery_address(&lottery_id, &id()).0;
+    let claim = instruction::claim(
+        &id(),
+        &lottery_id,
+        &lottery_id,
+        &buyer.pubkey(),
+        &payer,
+        &get_associated_token_address(&lottery_pda, &test.mint),
+        &fee_ata(&test),
+        &ticket_id,
+        &test.mint,
+        0,
+    );
+    let result = process(&mut test.context, &[claim], &[&buyer]).await;
+    assert_lottery_error(result, LotteryError::PrizeAlreadyPaid);
+
+    // the last tier takes what is left of the pot
+    let second_tier = instruction::withdraw(
+        &id(),
+        &lottery_id,
+        &lottery_id,
+        &payer,
+        &get_associated_token_address(&lottery_pda, &test.mint),
+        &fee_ata(&test),
+        &buyer.pubkey(),
+        &ticket_id,
+        &test.mint,
+        1,
+    );
+    process(&mut test.context, &[second_tier], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 10);
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Settled);
+}
+
+#[tokio::test]
+async fn test_refund_below_min_amount() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = Keypair::new();
+    let init = tiered_lottery_ix(&test, &lottery_id.pubkey(), vec![], 5, 0, 0);
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+    let lottery_id = lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 10).await;
+    process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 3)],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+
+    // refunds only open once the lottery is cancelled
+    let refund = refund_ix(&test, &lottery_id, &buyer.pubkey());
+    let result = process(&mut test.context, &[refund], &[&buyer]).await;
+    assert_lottery_error(result, LotteryError::NotCancelled);
+
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    test.context.warp_to_slot(lottery_info.ended_slot + 10).unwrap();
+    let payer = test.context.payer.pubkey();
+    process(&mut test.context, &[draw_ix(&lottery_id, &payer)], &[]).await.unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Cancelled);
+
+    let refund = refund_ix(&test, &lottery_id, &buyer.pubkey());
+    process(&mut test.context, &[refund], &[&buyer]).await.unwrap();
+    let buyer_ata = get_associated_token_address(&buyer.pubkey(), &test.mint);
+    assert_eq!(token_balance(&mut test.context, &buyer_ata).await, 10);
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.refunded_amount, 3);
+    assert_eq!(lottery_info.status, LotteryStatus::Settled);
+}
+
+#[tokio::test]
+async fn test_buy_over_wallet_cap() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = Keypair::new();
+    let init = tiered_lottery_ix(&test, &lottery_id.pubkey(), vec![], 0, 3, 0);
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+    let lottery_id = lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 10).await;
+    process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 2)],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+
+    // the cap counts every purchase of the wallet
+    let result = process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 1, 2)],
+        &[&buyer],
+    )
+    .await;
+    assert_lottery_error(result, LotteryError::OverWalletCap);
+    process(
+        &mut test.context,
+        &[buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 1, 1)],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.current_amount, 3);
+}
+
+#[tokio::test]
+async fn test_init_invalid_crank_reward() {
+    let mut test = setup(10, 100).await;
+    // whoever draws an uncommitted lottery could retry until the numbers suit them
+    let lottery_id = Keypair::new();
+    let init = tiered_lottery_ix(&test, &lottery_id.pubkey(), vec![], 0, 0, 1);
+    let result = process(&mut test.context, &[init], &[&lottery_id]).await;
+    assert_lottery_error(result, LotteryError::InvalidCrankReward);
+
+    // more than a ticket is worth would let the authority crank the pot away
+    let lottery_id = Keypair::new();
+    let init = committed_lottery_ix(&test, &lottery_id.pubkey(), &[7; 32], 2);
+    let result = process(&mut test.context, &[init], &[&lottery_id]).await;
+    assert_lottery_error(result, LotteryError::InvalidCrankReward);
+}
+
+#[tokio::test]
+async fn test_reveal_crank_reward() {
+    let mut test = setup(10, 100).await;
+    let seed = [7; 32];
+    let lottery_id = Keypair::new();
+    let init = committed_lottery_ix(&test, &lottery_id.pubkey(), &seed, 1);
+    process(&mut test.context, &[init], &[&lottery_id]).await.unwrap();
+    let lottery_id = lottery_id.pubkey();
+    let buyer = new_buyer(&mut test, 10).await;
+    let payer = test.context.payer.pubkey();
+    process(
+        &mut test.context,
+        &[
+            buy_ix(&lottery_id, &test.mint, &buyer.pubkey(), 0, 10),
+            draw_ix(&lottery_id, &payer),
+        ],
+        &[&buyer],
+    )
+    .await
+    .unwrap();
+    let lottery_vault = vault(&test, &lottery_id);
+    assert_eq!(token_balance(&mut test.context, &lottery_vault).await, 10);
+
+    // anyone holding the seed can reveal it and gets paid for it
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    test.context.warp_to_slot(lottery_info.reveal_slot() + 1).unwrap();
+    let cranker = new_buyer(&mut test, 0).await;
+    let cranker_ata = get_associated_token_address(&cranker.pubkey(), &test.mint);
+    let reveal = instruction::reveal(
+        &id(),
+        &lottery_id,
+        &cranker.pubkey(),
+        seed,
+        Some((&lottery_id, &lottery_vault, &cranker_ata, &test.mint)),
+    );
+    process(&mut test.context, &[reveal], &[&cranker]).await.unwrap();
+    assert_eq!(token_balance(&mut test.context, &cranker_ata).await, 1);
+    assert_eq!(token_balance(&mut test.context, &lottery_vault).await, 9);
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Drawn);
+}
+
+#[tokio::test]
+async fn test_claim() {
+    let mut test = setup(10, 100).await;
+    let lottery_id = test.lottery_id.pubkey();
+    let ((winner, winner_ticket), (loser, loser_ticket)) = drawn_lottery(&mut test).await;
+    let payer = test.context.payer.pubkey();
+    let lottery_vault = vault(&test, &lottery_id);
+    let fee_account = fee_ata(&test);
+    let mint = test.mint;
+    let claim_ix = |buyer: &Pubkey, ticket_id: &Pubkey| {
+        instruction::claim(
+            &id(),
+            &lottery_id,
+            &lottery_id,
+            buyer,
+            &payer,
+            &lottery_vault,
+            &fee_account,
+            ticket_id,
+            &mint,
+            0,
+        )
+    };
+
+    let claim = claim_ix(&loser.pubkey(), &loser_ticket);
+    let result = process(&mut test.context, &[claim], &[&loser]).await;
+    assert_lottery_error(result, LotteryError::WrongWinner);
+
+    // the winner collects without the authority signing
+    let claim = claim_ix(&winner.pubkey(), &winner_ticket);
+    process(&mut test.context, &[claim], &[&winner]).await.unwrap();
+    let winner_ata = get_associated_token_address(&winner.pubkey(), &test.mint);
+    let fee = 10 * FEE_BPS as u64 / 10000;
+    assert_eq!(token_balance(&mut test.context, &winner_ata).await, 10 - fee);
+    let lottery_info = get_lottery(&mut test.context, &lottery_id).await;
+    assert_eq!(lottery_info.status, LotteryStatus::Settled);
+}
+
+#[tokio::test]
+async fn test_migrate_legacy_lottery() {
+    // a settled lottery in the 161 byte layout of the first release, status byte first
+    let lottery_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let token_mint = Pubkey::new_unique();
+    let mut data = vec![0u8; LEGACY_LOTTERY_LEN];
+    data[0] = LotteryStatus::Settled as u8;
+    data[1..33].copy_from_slice(&authority.to_bytes());
+    data[97..105].copy_from_slice(&100u64.to_le_bytes());
+    data[105..113].copy_from_slice(&500u64.to_le_bytes());
+    data[113..121].copy_from_slice(&42u64.to_le_bytes());
+    data[121..129].copy_from_slice(&100u64.to_le_bytes());
+    data[129..161].copy_from_slice(&token_mint.to_bytes());
+    let mut program_test =
+        ProgramTest::new("lottery_mechine", id(), processor!(Processor::process));
+    program_test.add_account(
+        lottery_id,
+        Account {
+            lamports: Rent::default().minimum_balance(LEGACY_LOTTERY_LEN),
+            data,
+            owner: id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    let mut context = program_test.start_with_context().await;
+
+    let payer = context.payer.pubkey();
+    let migrate = instruction::migrate(&id(), &lottery_id, &payer, None).unwrap();
+    process(&mut context, &[migrate], &[]).await.unwrap();
+    let account = context.banks_client.get_account(lottery_id).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), Lottery::LEN);
+    let lottery_info = Lottery::unpack(&account.data).unwrap();
+    assert_eq!(lottery_info.version, LOTTERY_VERSION);
+    assert_eq!(lottery_info.bump, find_lottery_address(&lottery_id, &id()).1);
+    assert_eq!(lottery_info.status, LotteryStatus::Settled);
+    assert_eq!(lottery_info.authority, authority);
+    assert_eq!(lottery_info.token_mint, token_mint);
+    assert_eq!(lottery_info.max_amount, 100);
+    assert_eq!(lottery_info.ended_slot, 500);
+    assert_eq!(lottery_info.winning_numbers[0], 42);
+    assert_eq!(lottery_info.current_amount, 100);
+
+    // already at the current layout, the transfer only keeps the transaction apart from the first
+    let migrate = instruction::migrate(&id(), &lottery_id, &payer, None).unwrap();
+    let transfer = system_instruction::transfer(&payer, &authority, 1_000_000);
+    let result = process(&mut context, &[migrate, transfer], &[]).await;
+    assert_matches!(
+        result,
+        Err(TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::AccountAlreadyInitialized
+        )))
+    );
+}
@@ -0,0 +1,58 @@
+use crate::lottery::lottery_program_id;
+use lottery_mechine::event::LotteryEvent;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+
+/// Events of a transaction's log messages in order, "Program data:" lines are only
+/// decoded while the lottery program is the one running so CPI'd programs can't fake them
+pub fn decode_logs(logs: &[String]) -> Vec<LotteryEvent> {
+    let program_id = lottery_program_id().to_string();
+    let mut invoked: Vec<&str> = vec![];
+    let mut events: Vec<LotteryEvent> = vec![];
+    for log in logs {
+        let rest = match log.strip_prefix("Program ") {
+            Some(rest) => rest,
+            None => continue,
+        };
+        if let Some(data) = rest.strip_prefix("data: ") {
+            if invoked.last() != Some(&program_id.as_str()) {
+                continue;
+            }
+            let data = data.split(' ').next().unwrap_or("");
+            if let Some(event) = base64::decode(data)
+                .ok()
+                .and_then(|d| LotteryEvent::unpack(&d).ok())
+            {
+                events.push(event);
+            }
+            continue;
+        }
+        if rest.starts_with("log: ") {
+            continue;
+        }
+        let mut words = rest.split(' ');
+        let id = words.next().unwrap_or("");
+        match words.next() {
+            Some("invoke") => invoked.push(id),
+            Some("success") | Some("failed:") => {
+                invoked.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Fetches a confirmed transaction and decodes the lottery events it logged
+pub fn transaction_events(signature: &Signature, connection: &RpcClient) -> Vec<LotteryEvent> {
+    let transaction = connection
+        .get_transaction(signature, UiTransactionEncoding::Json)
+        .unwrap();
+    let logs = transaction
+        .transaction
+        .meta
+        .and_then(|meta| meta.log_messages)
+        .unwrap_or_default();
+    decode_logs(&logs)
+}
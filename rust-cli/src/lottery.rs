@@ -5,135 +5,223 @@ use solana_client::{
     rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
     rpc_request::TokenAccountsFilter,
 };
-use solana_program::{program_pack::Pack, system_program};
+use lottery_mechine::{
+    find_buyer_record_address, find_config_address, find_round_address, find_ticket_address,
+    get_token_account_address, instruction,
+    state::{
+        AccountType, BuyerRecord, Config, Lottery, LotteryStatus, Series, Ticket, TicketStatus,
+        LEGACY_LOTTERY_LEN, LEGACY_TICKET_LEN, LOTTERY_STATUS_OFFSET,
+    },
+};
+use solana_program::program_pack::Pack;
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
+    account::from_account,
+    clock::Clock,
+    instruction::Instruction,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    sysvar::{clock, rent},
+    sysvar,
 };
 use spl_associated_token_account;
-use spl_token;
 use std::borrow::Borrow;
 
-use crate::util::{get_pub, getkey, Lottery, Ticket};
+use crate::util::get_pub;
 pub fn lottery_program_id() -> Pubkey {
-    get_pub("42hrGQzkPQMXTmtpsE9hb9D7dTffzYXgqC4DHUHubJSv")
+    lottery_mechine::id()
+}
+pub fn config_address() -> Pubkey {
+    find_config_address(&self::lottery_program_id()).0
+}
+pub fn buyer_record_address(lottery_id: &Pubkey, buyer: &Pubkey) -> Pubkey {
+    find_buyer_record_address(lottery_id, buyer, &self::lottery_program_id()).0
+}
+pub fn ticket_address(lottery_id: &Pubkey, buyer: &Pubkey, index: u64) -> Pubkey {
+    find_ticket_address(lottery_id, buyer, index, &self::lottery_program_id()).0
+}
+/// Number of Buy calls `buyer` made in a lottery, the index of their next ticket
+pub fn ticket_count(lottery_id: &Pubkey, buyer: &Pubkey, connection: &RpcClient) -> u64 {
+    match connection.get_account_data(&self::buyer_record_address(lottery_id, buyer)) {
+        Ok(record_data) => BuyerRecord::unpack(&record_data).unwrap().ticket_count,
+        Err(_) => 0,
+    }
+}
+pub fn get_config_info(connection: &RpcClient) -> Config {
+    let config_data = connection.get_account_data(&self::config_address()).unwrap();
+    Config::unpack(&config_data).unwrap()
 }
 pub fn init_lottery(
     slot: u64,
     max_amount: u64,
+    commitment: [u8; 32],
+    prize_shares: &[u16],
+    min_amount: u64,
+    ticket_price: u64,
+    max_per_wallet: u64,
+    crank_reward: u64,
+    end_timestamp: i64,
+    start_slot: u64,
+    start_timestamp: i64,
+    rollover_window: i64,
     mint: &Pubkey,
     authority: &Pubkey,
+    connection: &RpcClient,
 ) -> (Vec<Instruction>, Keypair) {
-    let fee_receiver = get_pub("2wnEcArzCpX1QRdtpHRXxZ7k9b1UeK16mPt26LPWFZ6V");
-    let mut ins: Vec<Instruction> = vec![];
-    let mut lottery_key = Keypair::new();
-    while Pubkey::create_program_address(
-        &[&lottery_key.pubkey().to_bytes()],
+    let fee_receiver = self::get_config_info(connection).fee_receiver;
+    let lottery_key = Keypair::new();
+    let init_lottery_ins = instruction::init_lottery(
         &self::lottery_program_id(),
-    )
-    .is_err()
-    {
-        lottery_key = Keypair::new();
-    }
-    let lottery_pda = Pubkey::create_program_address(
-        &[&lottery_key.pubkey().to_bytes()],
+        &lottery_key.pubkey(),
+        authority,
+        &fee_receiver,
+        mint,
+        max_amount,
+        slot,
+        commitment,
+        prize_shares.to_vec(),
+        min_amount,
+        ticket_price,
+        max_per_wallet,
+        crank_reward,
+        end_timestamp,
+        start_slot,
+        start_timestamp,
+        rollover_window,
+    );
+
+    (vec![init_lottery_ins], lottery_key)
+}
+
+pub fn init_series(
+    slot: u64,
+    duration: i64,
+    max_amount: u64,
+    prize_shares: &[u16],
+    min_amount: u64,
+    ticket_price: u64,
+    max_per_wallet: u64,
+    rollover_window: i64,
+    mint: &Pubkey,
+    authority: &Pubkey,
+    connection: &RpcClient,
+) -> (Vec<Instruction>, Keypair) {
+    let fee_receiver = self::get_config_info(connection).fee_receiver;
+    let series_key = Keypair::new();
+    let init_series_ins = instruction::init_series(
         &self::lottery_program_id(),
-    )
-    .unwrap();
-    let mut data: Vec<u8> = vec![];
-    let mut keys: Vec<AccountMeta> = vec![];
-    keys.push(getkey(lottery_key.pubkey(), true, true));
-    keys.push(getkey(authority.clone(), true, true));
-    keys.push(getkey(fee_receiver, false, false));
-
-    keys.push(getkey(lottery_pda, false, false));
-    let lottery_ata =
-        spl_associated_token_account::get_associated_token_address(&lottery_pda, mint);
-    keys.push(getkey(lottery_ata, false, true));
-
-    let fee_ata = spl_associated_token_account::get_associated_token_address(&fee_receiver, mint);
-    keys.push(getkey(fee_ata, false, true));
-    keys.push(getkey(spl_associated_token_account::id(), false, false));
-    keys.push(getkey(mint.clone(), false, false));
-    keys.push(getkey(spl_token::id(), false, false));
-    keys.push(getkey(system_program::id(), false, false));
-    keys.push(getkey(clock::id(), false, false));
-
-    keys.push(getkey(rent::id(), false, false));
-
-    data.push(0);
-
-    data.extend_from_slice(&max_amount.to_le_bytes());
-
-    data.extend_from_slice(&slot.to_le_bytes());
-    let init_lottery_ins = Instruction {
-        program_id: self::lottery_program_id(),
-        data: data,
-        accounts: keys,
-    };
-    ins.push(init_lottery_ins);
+        &series_key.pubkey(),
+        authority,
+        &fee_receiver,
+        mint,
+        max_amount,
+        slot,
+        prize_shares.to_vec(),
+        min_amount,
+        ticket_price,
+        max_per_wallet,
+        0,
+        duration,
+        rollover_window,
+    );
+
+    (vec![init_series_ins], series_key)
+}
+
+/// Opens the series' next round, returns its lottery id
+pub fn next_round(
+    series_id: &Pubkey,
+    payer: &Pubkey,
+    connection: &RpcClient,
+) -> (Vec<Instruction>, Pubkey) {
+    let series_info = self::get_series_info(series_id, connection);
+    let round_id =
+        find_round_address(series_id, series_info.round, &self::lottery_program_id()).0;
+    let next_round_ins = instruction::next_round(
+        &self::lottery_program_id(),
+        series_id,
+        payer,
+        series_info.round,
+    );
 
-    (ins, lottery_key)
+    (vec![next_round_ins], round_id)
+}
+
+pub fn init_config(
+    admin: &Pubkey,
+    fee_receiver: &Pubkey,
+    fee_bps: u16,
+    max_fee_bps: u16,
+) -> Vec<Instruction> {
+    vec![instruction::init_config(
+        &self::lottery_program_id(),
+        admin,
+        fee_receiver,
+        fee_bps,
+        max_fee_bps,
+    )]
+}
+
+pub fn set_config(
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+    fee_receiver: &Pubkey,
+    fee_bps: u16,
+    max_fee_bps: u16,
+) -> Vec<Instruction> {
+    vec![instruction::set_config(
+        &self::lottery_program_id(),
+        admin,
+        new_admin,
+        fee_receiver,
+        fee_bps,
+        max_fee_bps,
+    )]
 }
 
 pub fn buy(
     lottery_id: &Pubkey,
     amount: u64,
+    partial: bool,
     authority: &Pubkey,
     rpc_client: &RpcClient,
-) -> (Vec<Instruction>, Keypair) {
-    let mut ins: Vec<Instruction> = vec![];
-    let ticket_key = Keypair::new();
+) -> (Vec<Instruction>, Pubkey) {
+    let ticket_index = self::ticket_count(lottery_id, authority, rpc_client);
+    let ticket_key = self::ticket_address(lottery_id, authority, ticket_index);
     let lottery_data = rpc_client.get_account_data(lottery_id.borrow()).unwrap();
-    let lottery_info = Lottery::unpack_unchecked(&lottery_data).unwrap();
-    let mint = rpc_client
-        .get_token_account(lottery_info.token_reciever.borrow())
-        .unwrap()
-        .unwrap();
-    let buyer_token_accounts = rpc_client
-        .get_token_accounts_by_owner(
-            authority.borrow(),
-            TokenAccountsFilter::Mint(get_pub(&mint.mint)),
-        )
-        .unwrap();
-
-    let buyer_token_account = get_pub(&(buyer_token_accounts[0].pubkey));
-    let mut data: Vec<u8> = vec![];
-    let mut keys: Vec<AccountMeta> = vec![];
+    let lottery_info = Lottery::unpack_versioned(&lottery_data).unwrap();
+    // a native SOL lottery takes the lamports straight from the wallet
+    let buyer_token_account = if lottery_info.is_native() {
+        authority.clone()
+    } else {
+        let buyer_token_accounts = rpc_client
+            .get_token_accounts_by_owner(
+                authority.borrow(),
+                TokenAccountsFilter::Mint(lottery_info.token_mint),
+            )
+            .unwrap();
+        get_pub(&(buyer_token_accounts[0].pubkey))
+    };
 
     //println!("Lottery info current :{:?},  max:{:?}",lottery_info.current_amount, lottery_info.max_amount);
 
-    data.push(1);
-    data.extend_from_slice(&amount.to_le_bytes());
-
-    keys.push(getkey(lottery_id.clone(), false, true));
-    keys.push(getkey(ticket_key.pubkey(), true, true));
-    keys.push(getkey(authority.clone(), true, false));
-    keys.push(getkey(lottery_info.token_reciever.clone(), false, true));
-    keys.push(getkey(buyer_token_account, false, true));
-    keys.push(getkey(spl_token::id(), false, false));
-    keys.push(getkey(clock::id(), false, false));
-
-    keys.push(getkey(system_program::id(), false, false));
-
-    keys.push(getkey(rent::id(), false, false));
-    let buy_ins = Instruction {
-        program_id: self::lottery_program_id(),
-        data: data,
-        accounts: keys,
-    };
-    ins.push(buy_ins);
-
-    (ins, ticket_key)
+    let buy_ins = instruction::buy(
+        &self::lottery_program_id(),
+        lottery_id,
+        authority,
+        &buyer_token_account,
+        &lottery_info.token_reciever,
+        &lottery_info.token_mint,
+        ticket_index,
+        amount,
+        partial,
+    );
+
+    (vec![buy_ins], ticket_key)
 }
 
 pub fn findtickets(lottery_id: &Pubkey, connection: &RpcClient) -> Vec<(u64, u64, Pubkey, Pubkey)> {
     let mut ticket_data: Vec<(u64, u64, Pubkey, Pubkey)> = vec![];
-    let ticket_program_id = get_pub("42hrGQzkPQMXTmtpsE9hb9D7dTffzYXgqC4DHUHubJSv");
 
-    let mut mem: Vec<u8> = vec![2];
+    let mut mem: Vec<u8> = vec![AccountType::Ticket as u8];
     mem.extend_from_slice(&lottery_id.to_bytes());
     let memcmp = MemcmpEncodedBytes::Base58(bs58::encode(mem).into_string());
     //println!("memcmp: {:?}\n",memcmp);
@@ -153,12 +241,12 @@ pub fn findtickets(lottery_id: &Pubkey, connection: &RpcClient) -> Vec<(u64, u64
     };
 
     let accounts = connection
-        .get_program_accounts_with_config(&ticket_program_id, config)
+        .get_program_accounts_with_config(&self::lottery_program_id(), config)
         .unwrap();
     //println!("{:?}",accounts);
     for data in accounts {
         let account = data.1;
-        let current_ticket = Ticket::unpack_unchecked(&account.data).unwrap();
+        let current_ticket = Ticket::unpack_versioned(&account.data).unwrap();
         let strart_number = current_ticket.start_number;
         let end_number = current_ticket.end_number;
         let ticket_buyer = current_ticket.buyer;
@@ -169,101 +257,235 @@ pub fn findtickets(lottery_id: &Pubkey, connection: &RpcClient) -> Vec<(u64, u64
     ticket_data
 }
 
-pub fn draw(lottery_id: &Pubkey, authority: &Pubkey) -> Vec<Instruction> {
+pub fn draw(lottery_id: &Pubkey, caller: &Pubkey) -> Vec<Instruction> {
+    vec![instruction::draw(
+        &self::lottery_program_id(),
+        lottery_id,
+        caller,
+    )]
+}
+
+pub fn reveal(
+    lottery_id: &Pubkey,
+    caller: &Pubkey,
+    seed: &[u8; 32],
+    connection: &RpcClient,
+) -> Vec<Instruction> {
+    let lottery_info = self::get_lottery_info(lottery_id, connection);
+    let vault_id = lottery_info.vault_id(lottery_id);
     let mut ins: Vec<Instruction> = vec![];
-    let mut data: Vec<u8> = vec![];
-    let mut keys: Vec<AccountMeta> = vec![];
-    keys.push(getkey(lottery_id.clone(), false, true));
-    keys.push(getkey(authority.clone(), true, true));
-    keys.push(getkey(clock::id(), false, false));
-    keys.push(getkey(
-        solana_program::sysvar::slot_hashes::id(),
-        false,
-        false,
-    ));
-    data.push(2);
-    let draw_ins = Instruction {
-        program_id: self::lottery_program_id(),
-        data: data,
-        accounts: keys,
+    let caller_ata = get_token_account_address(caller, &lottery_info.token_mint);
+    let crank_reward = if lottery_info.crank_reward != 0 {
+        if !lottery_info.is_native() && connection.get_account(&caller_ata).is_err() {
+            ins.push(
+                spl_associated_token_account::create_associated_token_account(
+                    caller,
+                    caller,
+                    &lottery_info.token_mint,
+                ),
+            );
+        }
+        Some((
+            &vault_id,
+            &lottery_info.token_reciever,
+            &caller_ata,
+            &lottery_info.token_mint,
+        ))
+    } else {
+        None
     };
-    ins.push(draw_ins);
+    ins.push(instruction::reveal(
+        &self::lottery_program_id(),
+        lottery_id,
+        caller,
+        *seed,
+        crank_reward,
+    ));
     ins
 }
 
 pub fn withdraw(
     lottery_id: &Pubkey,
+    prize_index: u8,
     authority: &Pubkey,
     connection: &RpcClient,
 ) -> Vec<Instruction> {
-    let lottery_info = self::get_lottery_info(lottery_id, connection);
+    self::payout(lottery_id, prize_index, authority, false, connection)
+}
 
-    let mut ins: Vec<Instruction> = vec![];
-    let mut data: Vec<u8> = vec![];
-    let mut keys: Vec<AccountMeta> = vec![];
+/// Claim a prize tier as the buyer of its winning ticket
+pub fn claim(
+    lottery_id: &Pubkey,
+    prize_index: u8,
+    winner: &Pubkey,
+    connection: &RpcClient,
+) -> Vec<Instruction> {
+    self::payout(lottery_id, prize_index, winner, true, connection)
+}
 
-    let (winner_ticket_id, winning_buyer) = self::find_winning_ticket(lottery_id, connection);
-    let lottery_ata_info = connection
-        .get_account_data(&lottery_info.token_reciever)
-        .unwrap();
-    let mint = spl_token::state::Account::unpack_unchecked(&lottery_ata_info)
-        .unwrap()
-        .mint;
-    println!("{:?}", winner_ticket_id);
-    let lottery_pda =
-        Pubkey::create_program_address(&[&lottery_id.to_bytes()], &self::lottery_program_id())
-            .unwrap();
-    let winner_ata =
-        spl_associated_token_account::get_associated_token_address(&winning_buyer, &mint);
-    keys.push(getkey(lottery_id.clone(), false, true));
-    keys.push(getkey(authority.clone(), true, true));
-    keys.push(getkey(lottery_info.token_reciever.clone(), false, true));
-    keys.push(getkey(lottery_info.fee_reciever.clone(), false, true));
-    keys.push(getkey(winner_ata.clone(), false, true));
-    keys.push(getkey(winner_ticket_id.clone(), false, false));
-    keys.push(getkey(lottery_pda.clone(), false, false));
-    keys.push(getkey(mint.clone(), false, false));
-    keys.push(getkey(spl_token::id().clone(), false, false));
-    keys.push(getkey(system_program::id().clone(), false, false));
-    keys.push(getkey(rent::id(), false, false));
-    keys.push(getkey(spl_associated_token_account::id(), false, false));
-    keys.push(getkey(winning_buyer.clone(), false, false));
-
-    data.push(3);
-    let withdraw_ins = Instruction {
-        program_id: self::lottery_program_id(),
-        data: data,
-        accounts: keys,
+fn payout(
+    lottery_id: &Pubkey,
+    prize_index: u8,
+    signer: &Pubkey,
+    by_winner: bool,
+    connection: &RpcClient,
+) -> Vec<Instruction> {
+    let lottery_info = self::get_lottery_info(lottery_id, connection);
+
+    let (winner_ticket_id, winning_buyer) = self::find_winning_ticket(
+        lottery_id,
+        lottery_info.winning_numbers[prize_index as usize],
+        connection,
+    );
+    let mint = lottery_info.token_mint;
+    let withdraw_ins = if by_winner {
+        instruction::claim(
+            &self::lottery_program_id(),
+            lottery_id,
+            &lottery_info.vault_id(lottery_id),
+            signer,
+            &lottery_info.authority,
+            &lottery_info.token_reciever,
+            &lottery_info.fee_reciever,
+            &winner_ticket_id,
+            &mint,
+            prize_index,
+        )
+    } else {
+        instruction::withdraw(
+            &self::lottery_program_id(),
+            lottery_id,
+            &lottery_info.vault_id(lottery_id),
+            signer,
+            &lottery_info.token_reciever,
+            &lottery_info.fee_reciever,
+            &winning_buyer,
+            &winner_ticket_id,
+            &mint,
+            prize_index,
+        )
     };
-    ins.push(withdraw_ins);
-    ins
+    vec![withdraw_ins]
 }
 
 pub fn close(ticket_id: &Pubkey, lottery_id: &Pubkey, buyer: &Pubkey) -> Vec<Instruction> {
+    vec![instruction::close(
+        &self::lottery_program_id(),
+        lottery_id,
+        ticket_id,
+        buyer,
+    )]
+}
+
+pub fn refund(lottery_id: &Pubkey, buyer: &Pubkey, connection: &RpcClient) -> Vec<Instruction> {
+    let lottery_info = self::get_lottery_info(lottery_id, connection);
+    let buyer_token_account = get_token_account_address(buyer, &lottery_info.token_mint);
+
     let mut ins: Vec<Instruction> = vec![];
-    let mut data: Vec<u8> = vec![];
-    let mut keys: Vec<AccountMeta> = vec![];
-    keys.push(getkey(lottery_id.clone(), false, false));
-    keys.push(getkey(ticket_id.clone(), false, true));
-    keys.push(getkey(buyer.clone(), false, true));
-
-    data.push(4);
-    let withdraw_ins = Instruction {
-        program_id: self::lottery_program_id(),
-        data: data,
-        accounts: keys,
-    };
-    ins.push(withdraw_ins);
+    for ticket in self::findtickets(lottery_id, connection) {
+        if ticket.2 != *buyer {
+            continue;
+        }
+        ins.push(instruction::refund(
+            &self::lottery_program_id(),
+            lottery_id,
+            &lottery_info.vault_id(lottery_id),
+            &ticket.3,
+            buyer,
+            &buyer_token_account,
+            &lottery_info.token_reciever,
+            &lottery_info.token_mint,
+            &lottery_info.authority,
+        ));
+    }
     ins
 }
 
-pub fn find_winning_ticket(lottery_id: &Pubkey, connection: &RpcClient) -> (Pubkey, Pubkey) {
+/// Rolls the pot of a lottery over into `successor_id`, a series round always rolls into the next round
+pub fn rollover(
+    lottery_id: &Pubkey,
+    successor_id: Option<&Pubkey>,
+    authority: &Pubkey,
+    connection: &RpcClient,
+) -> Vec<Instruction> {
     let lottery_info = self::get_lottery_info(lottery_id, connection);
+    let (successor_id, successor_token_reciever) = if lottery_info.series != Pubkey::default() {
+        (lottery_info.series, lottery_info.token_reciever)
+    } else {
+        let successor_id = successor_id.expect("a successor lottery is needed").clone();
+        let successor_info = self::get_lottery_info(&successor_id, connection);
+        (successor_id, successor_info.token_reciever)
+    };
+    vec![instruction::rollover(
+        &self::lottery_program_id(),
+        lottery_id,
+        &lottery_info.vault_id(lottery_id),
+        authority,
+        &lottery_info.token_reciever,
+        &lottery_info.token_mint,
+        &successor_id,
+        &successor_token_reciever,
+    )]
+}
+
+pub fn set_authority(
+    lottery_id: &Pubkey,
+    authority: &Pubkey,
+    new_authority: &Pubkey,
+    propose: bool,
+) -> Vec<Instruction> {
+    vec![instruction::set_authority(
+        &self::lottery_program_id(),
+        lottery_id,
+        authority,
+        new_authority,
+        propose,
+    )]
+}
+
+pub fn accept_authority(lottery_id: &Pubkey, pending_authority: &Pubkey) -> Vec<Instruction> {
+    vec![instruction::accept_authority(
+        &self::lottery_program_id(),
+        lottery_id,
+        pending_authority,
+    )]
+}
+
+/// UpdateLottery keeping every value that isn't given, `extra_slots` is added to the ended slot
+pub fn update_lottery(
+    lottery_id: &Pubkey,
+    authority: &Pubkey,
+    max_amount: Option<u64>,
+    extra_slots: u64,
+    end_timestamp: Option<i64>,
+    connection: &RpcClient,
+) -> Vec<Instruction> {
+    let ended_slot = if extra_slots == 0 {
+        0
+    } else {
+        let lottery_info = self::get_lottery_info(lottery_id, connection);
+        lottery_info.ended_slot.saturating_add(extra_slots)
+    };
+    vec![instruction::update_lottery(
+        &self::lottery_program_id(),
+        lottery_id,
+        authority,
+        max_amount.unwrap_or(0),
+        ended_slot,
+        end_timestamp.unwrap_or(0),
+    )]
+}
+
+pub fn find_winning_ticket(
+    lottery_id: &Pubkey,
+    winning_number: u64,
+    connection: &RpcClient,
+) -> (Pubkey, Pubkey) {
     let tickets = self::findtickets(lottery_id, connection);
     let mut winningticket = Pubkey::default();
     let mut winningticket_buyer = Pubkey::default();
     for data in tickets {
-        if lottery_info.lottery_number >= data.0 && lottery_info.lottery_number <= data.1 {
+        if winning_number >= data.0 && winning_number <= data.1 {
             winningticket_buyer = data.2;
             winningticket = data.3;
         }
@@ -271,26 +493,71 @@ pub fn find_winning_ticket(lottery_id: &Pubkey, connection: &RpcClient) -> (Pubk
     (winningticket, winningticket_buyer)
 }
 
+/// Unpaid prize tiers won by one of `buyer`'s tickets
+pub fn claimable_prizes(lottery_id: &Pubkey, buyer: &Pubkey, connection: &RpcClient) -> Vec<u8> {
+    let lottery_info = self::get_lottery_info(lottery_id, connection);
+    if lottery_info.status != LotteryStatus::Drawn || lottery_info.current_amount == 0 {
+        return vec![];
+    }
+    let tickets = self::findtickets(lottery_id, connection);
+    (0..lottery_info.prize_count)
+        .filter(|i| lottery_info.paid_prizes & (1 << i) == 0)
+        .filter(|i| {
+            let number = lottery_info.winning_numbers[*i as usize];
+            tickets
+                .iter()
+                .any(|t| t.2 == *buyer && number >= t.0 && number <= t.1)
+        })
+        .collect()
+}
+
+/// Prize tiers of a drawn lottery that haven't been paid yet
+pub fn unpaid_prizes(lottery_id: &Pubkey, connection: &RpcClient) -> Vec<u8> {
+    let lottery_info = self::get_lottery_info(lottery_id, connection);
+    if lottery_info.current_amount == 0 {
+        // nothing sold, a single withdraw just closes the vault
+        return vec![0];
+    }
+    (0..lottery_info.prize_count)
+        .filter(|i| lottery_info.paid_prizes & (1 << i) == 0)
+        .collect()
+}
+
 pub fn get_lottery_info(lottery_id: &Pubkey, connection: &RpcClient) -> Lottery {
     let lottery_data = connection.get_account_data(lottery_id.borrow()).unwrap();
-    let lottery_info = Lottery::unpack_unchecked(&lottery_data).unwrap();
+    let lottery_info = Lottery::unpack_versioned(&lottery_data).unwrap();
     lottery_info
 }
 
-pub fn get_ended_lotterys(pool_authority: &Pubkey, connection: &RpcClient) -> Vec<Pubkey> {
-    let ticket_program_id = get_pub("42hrGQzkPQMXTmtpsE9hb9D7dTffzYXgqC4DHUHubJSv");
-    let mut mem: Vec<u8> = vec![1];
-    mem.extend_from_slice(&pool_authority.to_bytes());
-    let memcmp = MemcmpEncodedBytes::Base58(bs58::encode(mem).into_string());
-    //println!("memcmp: {:?}\n",memcmp);
-    let filter = Some(vec![
+pub fn get_series_info(series_id: &Pubkey, connection: &RpcClient) -> Series {
+    let series_data = connection.get_account_data(series_id).unwrap();
+    Series::unpack(&series_data).unwrap()
+}
+
+/// Lotteries in `status`, of every authority when `pool_authority` is None
+fn get_lotterys_with_status(
+    pool_authority: Option<&Pubkey>,
+    status: LotteryStatus,
+    connection: &RpcClient,
+) -> Vec<(Pubkey, Lottery)> {
+    let status_cmp = MemcmpEncodedBytes::Base58(bs58::encode(vec![status as u8]).into_string());
+    let mut filters = vec![
+        RpcFilterType::DataSize(Lottery::LEN as u64),
         RpcFilterType::Memcmp(Memcmp {
-            offset: 0,
-            bytes: memcmp,
+            offset: LOTTERY_STATUS_OFFSET,
+            bytes: status_cmp,
             encoding: None,
         }),
-        RpcFilterType::DataSize(161),
-    ]);
+    ];
+    if let Some(pool_authority) = pool_authority {
+        let memcmp = MemcmpEncodedBytes::Base58(bs58::encode(pool_authority.to_bytes()).into_string());
+        filters.push(RpcFilterType::Memcmp(Memcmp {
+            offset: 1,
+            bytes: memcmp,
+            encoding: None,
+        }));
+    }
+    let filter = Some(filters);
 
     let config = RpcProgramAccountsConfig {
         filters: filter,
@@ -300,20 +567,28 @@ pub fn get_ended_lotterys(pool_authority: &Pubkey, connection: &RpcClient) -> Ve
         },
         with_context: None,
     };
-    let slot = connection.get_slot().unwrap();
-    let mut lotteys: Vec<Pubkey> = vec![];
     let accounts = connection
-        .get_program_accounts_with_config(&ticket_program_id, config)
+        .get_program_accounts_with_config(&self::lottery_program_id(), config)
         .unwrap();
-    for data in accounts {
-        let account = data.1;
-        let current_ticket = Lottery::unpack_unchecked(&account.data).unwrap();
-        let max_amount = current_ticket.max_amount;
-        let current_amount = current_ticket.current_amount;
-        let ended_slot = current_ticket.ended_slot;
-        // print!("{:?}, {:?} , {:?}\n",data.0,max_amount,current_amount);
-        if slot > ended_slot || current_amount > max_amount {
-            lotteys.push(data.0);
+    accounts
+        .into_iter()
+        .map(|(lottery_id, account)| (lottery_id, Lottery::unpack_unchecked(&account.data).unwrap()))
+        .collect()
+}
+
+/// Open lotteries ready to draw, of every authority when `pool_authority` is None
+pub fn get_ended_lotterys(pool_authority: Option<&Pubkey>, connection: &RpcClient) -> Vec<Pubkey> {
+    let clock: Clock = from_account(&connection.get_account(&sysvar::clock::id()).unwrap()).unwrap();
+    let mut lotteys: Vec<Pubkey> =
+        self::get_lotterys_with_status(pool_authority, LotteryStatus::SoldOut, connection)
+            .into_iter()
+            .map(|(lottery_id, _)| lottery_id)
+            .collect();
+    for (lottery_id, lottery_info) in
+        self::get_lotterys_with_status(pool_authority, LotteryStatus::Open, connection)
+    {
+        if lottery_info.has_ended(&clock) {
+            lotteys.push(lottery_id);
         }
     }
     lotteys.sort();
@@ -321,49 +596,18 @@ pub fn get_ended_lotterys(pool_authority: &Pubkey, connection: &RpcClient) -> Ve
 }
 
 pub fn get_withdrable_lotterys(pool_authority: &Pubkey, connection: &RpcClient) -> Vec<Pubkey> {
-    let ticket_program_id = get_pub("42hrGQzkPQMXTmtpsE9hb9D7dTffzYXgqC4DHUHubJSv");
-    let mut mem: Vec<u8> = vec![3];
-    mem.extend_from_slice(&pool_authority.to_bytes());
-    let memcmp = MemcmpEncodedBytes::Base58(bs58::encode(mem).into_string());
-    //println!("memcmp: {:?}\n",memcmp);
-    let filter = Some(vec![
-        RpcFilterType::Memcmp(Memcmp {
-            offset: 0,
-            bytes: memcmp,
-            encoding: None,
-        }),
-        RpcFilterType::DataSize(161),
-    ]);
-
-    let config = RpcProgramAccountsConfig {
-        filters: filter,
-        account_config: RpcAccountInfoConfig {
-            encoding: Some(UiAccountEncoding::Base64),
-            ..RpcAccountInfoConfig::default()
-        },
-        with_context: None,
-    };
-    let mut lotteys: Vec<Pubkey> = vec![];
-    let accounts = connection
-        .get_program_accounts_with_config(&ticket_program_id, config)
-        .unwrap();
-    for data in accounts {
-        let account = data.1;
-        let current_ticket = Lottery::unpack_unchecked(&account.data).unwrap();
-        let max_amount = current_ticket.max_amount;
-        let current_amount = current_ticket.current_amount;
-        print!("{:?}, {:?} , {:?}\n", data.0, max_amount, current_amount);
-
-        lotteys.push(data.0);
-    }
+    let mut lotteys: Vec<Pubkey> =
+        self::get_lotterys_with_status(Some(pool_authority), LotteryStatus::Drawn, connection)
+            .into_iter()
+            .map(|(lottery_id, _)| lottery_id)
+            .collect();
     lotteys.sort();
     lotteys
 }
 
 pub fn find_closable_tickets(buyer: &Pubkey, connection: &RpcClient) -> Vec<(Pubkey, Pubkey)> {
-    let ticket_program_id = get_pub("42hrGQzkPQMXTmtpsE9hb9D7dTffzYXgqC4DHUHubJSv");
     let mut mem: Vec<u8> = vec![];
-    let account_type: Vec<u8> = vec![2];
+    let account_type: Vec<u8> = vec![AccountType::Ticket as u8];
     mem.extend_from_slice(&buyer.to_bytes());
     let memcmp = MemcmpEncodedBytes::Base58(bs58::encode(mem).into_string());
     let account_type_cmp = MemcmpEncodedBytes::Base58(bs58::encode(account_type).into_string());
@@ -391,17 +635,19 @@ pub fn find_closable_tickets(buyer: &Pubkey, connection: &RpcClient) -> Vec<(Pub
     };
     let mut tickets: Vec<(Pubkey, Pubkey)> = vec![];
     let accounts = connection
-        .get_program_accounts_with_config(&ticket_program_id, config)
+        .get_program_accounts_with_config(&self::lottery_program_id(), config)
         .unwrap();
     //println!("accounts: {:?}",accounts);
     for data in accounts {
         let account = data.1;
-        let current_ticket = Ticket::unpack_unchecked(&account.data).unwrap();
+        let current_ticket = Ticket::unpack_versioned(&account.data).unwrap();
         let ended_lottery_id = current_ticket.lottery_id;
         let ended_lottery_info = self::get_lottery_info(&ended_lottery_id, connection);
 
         //print!("{:?}, {:?} \n",data.0,ended_lottery_id);
-        if ended_lottery_info.account_type == 4 {
+        if ended_lottery_info.status == LotteryStatus::Settled
+            && current_ticket.status == TicketStatus::Active
+        {
             tickets.push((data.0, ended_lottery_id));
         }
     }
@@ -410,8 +656,13 @@ pub fn find_closable_tickets(buyer: &Pubkey, connection: &RpcClient) -> Vec<(Pub
 }
 
 pub fn find_all_closable_tickets(connection: &RpcClient) -> Vec<(Pubkey, Pubkey, Pubkey)> {
-    let ticket_program_id = get_pub("42hrGQzkPQMXTmtpsE9hb9D7dTffzYXgqC4DHUHubJSv");
-    let filter = Some(vec![RpcFilterType::DataSize(81)]);
+    let account_type =
+        MemcmpEncodedBytes::Base58(bs58::encode(vec![AccountType::Ticket as u8]).into_string());
+    let filter = Some(vec![RpcFilterType::Memcmp(Memcmp {
+        offset: 0,
+        bytes: account_type,
+        encoding: None,
+    })]);
 
     let config = RpcProgramAccountsConfig {
         filters: filter,
@@ -423,20 +674,77 @@ pub fn find_all_closable_tickets(connection: &RpcClient) -> Vec<(Pubkey, Pubkey,
     };
     let mut tickets: Vec<(Pubkey, Pubkey, Pubkey)> = vec![];
     let accounts = connection
-        .get_program_accounts_with_config(&ticket_program_id, config)
+        .get_program_accounts_with_config(&self::lottery_program_id(), config)
         .unwrap();
     //println!("accounts: {:?}",accounts);
     for data in accounts {
         let account = data.1;
-        let current_ticket = Ticket::unpack_unchecked(&account.data).unwrap();
+        let current_ticket = Ticket::unpack_versioned(&account.data).unwrap();
         let ended_lottery_id = current_ticket.lottery_id;
         let ended_lottery_info = self::get_lottery_info(&ended_lottery_id, connection);
 
         // print!("{:?}, {:?} \n",data.0,ended_lottery_id);
-        if ended_lottery_info.account_type == 4 {
+        if ended_lottery_info.status == LotteryStatus::Settled
+            && current_ticket.status == TicketStatus::Active
+        {
             tickets.push((data.0, ended_lottery_id, current_ticket.buyer));
         }
     }
     tickets.sort();
     tickets
 }
+
+/// Rewrites a legacy lottery and its legacy tickets into the current layouts
+pub fn migrate(lottery_id: &Pubkey, payer: &Pubkey, connection: &RpcClient) -> Vec<Instruction> {
+    let mut ins: Vec<Instruction> = vec![];
+    let lottery_data = connection.get_account_data(lottery_id).unwrap();
+    if lottery_data.len() == LEGACY_LOTTERY_LEN {
+        let lottery_info = Lottery::unpack_versioned(&lottery_data).unwrap();
+        let legacy_vault = if lottery_info.status != LotteryStatus::Settled {
+            Some((
+                &lottery_info.authority,
+                &lottery_info.token_reciever,
+                &lottery_info.token_mint,
+            ))
+        } else {
+            None
+        };
+        ins.push(instruction::migrate(
+            &self::lottery_program_id(),
+            lottery_id,
+            payer,
+            legacy_vault,
+        ).unwrap());
+    }
+
+    let mut mem: Vec<u8> = vec![AccountType::Ticket as u8];
+    mem.extend_from_slice(&lottery_id.to_bytes());
+    let filter = Some(vec![
+        RpcFilterType::Memcmp(Memcmp {
+            offset: 0,
+            bytes: MemcmpEncodedBytes::Base58(bs58::encode(mem).into_string()),
+            encoding: None,
+        }),
+        RpcFilterType::DataSize(LEGACY_TICKET_LEN as u64),
+    ]);
+    let config = RpcProgramAccountsConfig {
+        filters: filter,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        with_context: None,
+    };
+    let tickets = connection
+        .get_program_accounts_with_config(&self::lottery_program_id(), config)
+        .unwrap();
+    for (ticket_id, _) in tickets {
+        ins.push(instruction::migrate(
+            &self::lottery_program_id(),
+            &ticket_id,
+            payer,
+            None,
+        ).unwrap());
+    }
+    ins
+}
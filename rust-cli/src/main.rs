@@ -1,16 +1,21 @@
 use crate::util::get_pub;
 use base64::encode;
+use chrono::DateTime;
 use clap::{load_yaml, App};
 use solana_account_decoder::parse_token::spl_token_v2_0_native_mint;
 use solana_client::rpc_client::RpcClient;
-use solana_program::system_instruction;
+use solana_program::{system_instruction, system_program};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    hash::hash,
     instruction::Instruction,
-    signature::{Keypair, Signer},
+    signature::{Keypair, Signature, Signer},
     transaction::Transaction,
 };
 use std::borrow::Borrow;
+use std::convert::TryInto;
+use std::str::FromStr;
+mod event;
 mod lottery;
 mod util;
 
@@ -82,15 +87,52 @@ fn main() {
 
     if let Some(ref matches) = matches.subcommand_matches("init") {
         let lottery_max_amount: u64 = matches.value_of("max_amount").unwrap().parse().unwrap();
-        let slot_last: u64 = matches.value_of("slot_last").unwrap().parse().unwrap();
+        let slot_last: u64 = matches.value_of("slot_last").unwrap_or("0").parse().unwrap();
+        // the slot duration is ignored once an end time is set
+        let end_timestamp = matches
+            .value_of("end")
+            .map(|end| DateTime::parse_from_rfc3339(end).unwrap().timestamp())
+            .unwrap_or(0);
+        let start_timestamp = matches
+            .value_of("start")
+            .map(|start| DateTime::parse_from_rfc3339(start).unwrap().timestamp())
+            .unwrap_or(0);
+        let start_slot: u64 = matches.value_of("start_slot").unwrap_or("0").parse().unwrap();
         if matches.is_present("mint") {
             token_mint = util::get_pub(matches.value_of("mint").unwrap());
+        } else if matches.is_present("native") {
+            token_mint = system_program::id();
         }
+        let mut commitment = [0u8; 32];
+        if matches.is_present("commit") {
+            // a fresh random 32 bytes, only its hash goes on chain until the reveal
+            let seed = Keypair::new().pubkey().to_bytes();
+            commitment = hash(&seed).to_bytes();
+            println!(
+                "Draw seed (keep it to reveal): {}",
+                bs58::encode(seed).into_string()
+            );
+        }
+        let prize_shares = util::prize_shares(
+            matches.value_of("prizes"),
+            matches.value_of("winners").map(|w| w.parse().unwrap()),
+        );
         let (mut init_ins, lottery_signer) = lottery::init_lottery(
             slot_last,
             lottery_max_amount,
+            commitment,
+            &prize_shares,
+            matches.value_of("min_amount").unwrap_or("0").parse().unwrap(),
+            matches.value_of("price").unwrap_or("1").parse().unwrap(),
+            matches.value_of("max_per_wallet").unwrap_or("0").parse().unwrap(),
+            matches.value_of("crank_reward").unwrap_or("0").parse().unwrap(),
+            end_timestamp,
+            start_slot,
+            start_timestamp,
+            matches.value_of("rollover").unwrap_or("0").parse().unwrap(),
             &token_mint,
             &wallet_publickey,
+            rpc_client.borrow(),
         );
         println!(
             "Lottery initialized, id: {:?}",
@@ -99,29 +141,183 @@ fn main() {
         instruction_signer = lottery_signer;
         ins.append(&mut init_ins);
         signer.push(&instruction_signer);
+    } else if let Some(ref matches) = matches.subcommand_matches("init_series") {
+        let series_max_amount: u64 = matches.value_of("max_amount").unwrap().parse().unwrap();
+        let slot_last: u64 = matches.value_of("slot_last").unwrap_or("0").parse().unwrap();
+        let duration: i64 = matches.value_of("duration").unwrap_or("0").parse().unwrap();
+        if matches.is_present("mint") {
+            token_mint = util::get_pub(matches.value_of("mint").unwrap());
+        } else if matches.is_present("native") {
+            token_mint = system_program::id();
+        }
+        let prize_shares = util::prize_shares(
+            matches.value_of("prizes"),
+            matches.value_of("winners").map(|w| w.parse().unwrap()),
+        );
+        let (mut init_ins, series_signer) = lottery::init_series(
+            slot_last,
+            duration,
+            series_max_amount,
+            &prize_shares,
+            matches.value_of("min_amount").unwrap_or("0").parse().unwrap(),
+            matches.value_of("price").unwrap_or("1").parse().unwrap(),
+            matches.value_of("max_per_wallet").unwrap_or("0").parse().unwrap(),
+            matches.value_of("rollover").unwrap_or("0").parse().unwrap(),
+            &token_mint,
+            &wallet_publickey,
+            rpc_client.borrow(),
+        );
+        println!(
+            "Series initialized, id: {:?}",
+            series_signer.pubkey().clone()
+        );
+        instruction_signer = series_signer;
+        ins.append(&mut init_ins);
+        signer.push(&instruction_signer);
+    } else if let Some(ref matches) = matches.subcommand_matches("next_round") {
+        let series_id = get_pub(matches.value_of("series_id").unwrap());
+        let (mut round_ins, round_id) =
+            lottery::next_round(&series_id, &wallet_publickey, rpc_client.borrow());
+        println!("next round, lottery id: {:?}", round_id);
+        ins.append(&mut round_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("init_config") {
+        let fee_bps: u16 = matches.value_of("fee_bps").unwrap().parse().unwrap();
+        let max_fee_bps: u16 = matches.value_of("max_fee_bps").unwrap().parse().unwrap();
+        let fee_receiver = get_pub(matches.value_of("fee_receiver").unwrap());
+        let mut config_ins =
+            lottery::init_config(&wallet_publickey, &fee_receiver, fee_bps, max_fee_bps);
+        ins.append(&mut config_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("set_config") {
+        let config_info = lottery::get_config_info(rpc_client.borrow());
+        let fee_bps: u16 = match matches.value_of("fee_bps") {
+            Some(fee_bps) => fee_bps.parse().unwrap(),
+            None => config_info.fee_bps,
+        };
+        let max_fee_bps: u16 = match matches.value_of("max_fee_bps") {
+            Some(max_fee_bps) => max_fee_bps.parse().unwrap(),
+            None => config_info.max_fee_bps,
+        };
+        let fee_receiver = match matches.value_of("fee_receiver") {
+            Some(fee_receiver) => get_pub(fee_receiver),
+            None => config_info.fee_receiver,
+        };
+        let new_admin = match matches.value_of("admin") {
+            Some(admin) => get_pub(admin),
+            None => config_info.admin,
+        };
+        let mut config_ins = lottery::set_config(
+            &wallet_publickey,
+            &new_admin,
+            &fee_receiver,
+            fee_bps,
+            max_fee_bps,
+        );
+        ins.append(&mut config_ins);
     } else if let Some(ref matches) = matches.subcommand_matches("buy") {
         lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
         let value_amount = matches.value_of("amount").unwrap();
-        let ticket_buying_amount = value_amount.parse().unwrap();
-        let (mut buy_ins, ticket_signer) = lottery::buy(
+        let ticket_buying_amount: u64 = value_amount.parse().unwrap();
+        let lottery_info = lottery::get_lottery_info(&lottery_id, rpc_client.borrow());
+        println!(
+            "buying {:?} tickets for {:?} tokens",
+            ticket_buying_amount,
+            ticket_buying_amount * lottery_info.ticket_price
+        );
+        let (mut buy_ins, ticket_id) = lottery::buy(
             &lottery_id,
             ticket_buying_amount,
+            matches.is_present("partial"),
             &wallet_publickey,
             rpc_client.borrow(),
         );
-        println!("ticket bought, id: {:?}", ticket_signer.pubkey().clone());
-        instruction_signer = ticket_signer;
+        println!("ticket bought, id: {:?}", ticket_id);
         ins.append(&mut buy_ins);
-        signer.push(&instruction_signer);
     } else if let Some(ref matches) = matches.subcommand_matches("draw") {
         lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
         let mut draw_ins = lottery::draw(&lottery_id, &wallet_publickey);
         ins.append(&mut draw_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("reveal") {
+        lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
+        let seed: [u8; 32] = bs58::decode(matches.value_of("seed").unwrap())
+            .into_vec()
+            .unwrap()
+            .as_slice()
+            .try_into()
+            .expect("seed should be 32 bytes");
+        let mut reveal_ins =
+            lottery::reveal(&lottery_id, &wallet_publickey, &seed, rpc_client.borrow());
+        ins.append(&mut reveal_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("refund") {
+        lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
+        let mut refund_ins = lottery::refund(&lottery_id, &wallet_publickey, rpc_client.borrow());
+        ins.append(&mut refund_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("rollover") {
+        lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
+        let successor_id = matches.value_of("to").map(get_pub);
+        let mut rollover_ins = lottery::rollover(
+            &lottery_id,
+            successor_id.as_ref(),
+            &wallet_publickey,
+            rpc_client.borrow(),
+        );
+        ins.append(&mut rollover_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("set_authority") {
+        lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
+        let new_authority = get_pub(matches.value_of("new_authority").unwrap());
+        let mut authority_ins = lottery::set_authority(
+            &lottery_id,
+            &wallet_publickey,
+            &new_authority,
+            matches.is_present("propose"),
+        );
+        ins.append(&mut authority_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("update") {
+        lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
+        let max_amount = matches.value_of("max_amount").map(|amount| amount.parse().unwrap());
+        let extra_slots: u64 = matches.value_of("slot_last").unwrap_or("0").parse().unwrap();
+        let end_timestamp = matches
+            .value_of("end")
+            .map(|end| DateTime::parse_from_rfc3339(end).unwrap().timestamp());
+        let mut update_ins = lottery::update_lottery(
+            &lottery_id,
+            &wallet_publickey,
+            max_amount,
+            extra_slots,
+            end_timestamp,
+            rpc_client.borrow(),
+        );
+        ins.append(&mut update_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("accept_authority") {
+        lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
+        let mut authority_ins = lottery::accept_authority(&lottery_id, &wallet_publickey);
+        ins.append(&mut authority_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("migrate") {
+        lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
+        let migrate_ins = lottery::migrate(&lottery_id, &wallet_publickey, rpc_client.borrow());
+        println!("{:?} legacy accounts to migrate", migrate_ins.len());
+        // a lottery with many legacy tickets takes several runs
+        ins.extend(migrate_ins.into_iter().take(10));
     } else if let Some(ref matches) = matches.subcommand_matches("withdraw") {
         lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
-        let mut withdraw_ins =
-            lottery::withdraw(&lottery_id, &wallet_publickey, rpc_client.borrow());
+        let prize_index: u8 = matches.value_of("tier").unwrap_or("0").parse().unwrap();
+        let mut withdraw_ins = lottery::withdraw(
+            &lottery_id,
+            prize_index,
+            &wallet_publickey,
+            rpc_client.borrow(),
+        );
         ins.append(&mut withdraw_ins);
+    } else if let Some(ref matches) = matches.subcommand_matches("claim") {
+        lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
+        let prizes = lottery::claimable_prizes(&lottery_id, &wallet_publickey, rpc_client.borrow());
+        if prizes.is_empty() {
+            println!("no prize to claim in {:?}", lottery_id);
+        }
+        for prize_index in prizes {
+            let mut claim_ins =
+                lottery::claim(&lottery_id, prize_index, &wallet_publickey, rpc_client.borrow());
+            ins.append(&mut claim_ins);
+        }
     } else if let Some(ref _matches) = matches.subcommand_matches("find") {
         lottery_id = get_pub(matches.value_of("lottery_id").unwrap());
 
@@ -130,20 +326,38 @@ fn main() {
         for data in tickets {
             println!("{:?},{:?},{:?},{:?}", data.0, data.1, data.2, data.3);
         }
+    } else if let Some(ref matches) = matches.subcommand_matches("events") {
+        let signature = Signature::from_str(matches.value_of("signature").unwrap()).unwrap();
+        for event in event::transaction_events(&signature, rpc_client.borrow()) {
+            println!("{:?}", event);
+        }
     } else if let Some(ref _matches) = matches.subcommand_matches("draw_all") {
-        let ended_lotterys = lottery::get_ended_lotterys(&wallet_publickey, rpc_client.borrow());
+        let ended_lotterys =
+            lottery::get_ended_lotterys(Some(&wallet_publickey), rpc_client.borrow());
         for id in ended_lotterys {
             let mut draw_ins = lottery::draw(&id, &wallet_publickey);
             ins.append(&mut draw_ins);
         }
+    } else if let Some(ref _matches) = matches.subcommand_matches("crank") {
+        let ended_lotterys = lottery::get_ended_lotterys(None, rpc_client.borrow());
+        for id in ended_lotterys {
+            let mut draw_ins = lottery::draw(&id, &wallet_publickey);
+            ins.append(&mut draw_ins);
+            if ins.len() > 4 {
+                break;
+            }
+        }
     } else if let Some(ref _matches) = matches.subcommand_matches("withdraw_all") {
         let withdrawble_lottery =
             lottery::get_withdrable_lotterys(&wallet_publickey, rpc_client.borrow());
-        for id in withdrawble_lottery {
-            let mut withdraw_ins = lottery::withdraw(&id, &wallet_publickey, rpc_client.borrow());
-            ins.append(&mut withdraw_ins);
-            if ins.len() > 2 {
-                break;
+        'lotteries: for id in withdrawble_lottery {
+            for prize_index in lottery::unpaid_prizes(&id, rpc_client.borrow()) {
+                let mut withdraw_ins =
+                    lottery::withdraw(&id, prize_index, &wallet_publickey, rpc_client.borrow());
+                ins.append(&mut withdraw_ins);
+                if ins.len() > 2 {
+                    break 'lotteries;
+                }
             }
         }
     } else if let Some(ref _matches) = matches.subcommand_matches("close_all") {
@@ -171,8 +385,8 @@ fn main() {
     //print!("{:?},{:?}",get_ended_lotterys(&wallet_publickey, rpc_client.borrow()),wallet_publickey);
     if !ins.is_empty() {
         let mut tx = Transaction::new_with_payer(&ins, fee_payer);
-        let (recent, _fee) = rpc_client
-            .get_recent_blockhash()
+        let recent = rpc_client
+            .get_latest_blockhash()
             .expect("failed to get recent blockhash");
 
         tx.sign(&signer, recent);
@@ -181,6 +395,11 @@ fn main() {
         // this is the raw message of a tx, it's for debugging
 
         let send = rpc_client.send_and_confirm_transaction_with_spinner(&tx);
+        if let Err(ref error) = send {
+            if let Some(message) = util::lottery_error_message(error) {
+                println!("error: {}", message);
+            }
+        }
         println!("result:{:?}\n {:?} \n", send, messagee);
     }
 }
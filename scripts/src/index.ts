@@ -274,12 +274,13 @@ export async function buy(_lotteryPoolId: string, _amount: number) {
 
   // prepare data
   // referenced from program/src/instruction.rs
-  const dataLayout = struct([u8("instruction"), u64("amount")]);
+  const dataLayout = struct([u8("instruction"), u64("amount"), u8("partial")]);
   const data = Buffer.alloc(dataLayout.span);
   dataLayout.encode(
     {
       instruction: new BN(1), // for the instruction index, see instruction.rs
       amount: new BN(_amount),
+      partial: 0, // 1 to buy whatever is left instead of failing with LotterySoldOut
     },
     data
   );